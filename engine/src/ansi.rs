@@ -0,0 +1,209 @@
+use crate::settings::KeyCode;
+use crate::text::{Color, Terminal};
+use alloc::string::String;
+use core::fmt::Write;
+
+// Size assumed for a serial terminal, there is no reliable way to ask
+pub const SIZE: (usize, usize) = (80, 24);
+
+// Renders to anything that understands ANSI escape sequences, like a serial terminal
+pub struct AnsiTerminal<W: Write> {
+    out: W,
+    size: (usize, usize),
+    // Output is collected and sent at once on flush
+    buffer: String,
+    colors: Option<(Color, Color)>,
+}
+
+impl<W: Write> AnsiTerminal<W> {
+    pub fn new(out: W, size: (usize, usize)) -> Self {
+        Self {
+            out,
+            size,
+            buffer: String::new(),
+            colors: None,
+        }
+    }
+
+    pub fn output(&mut self) -> &mut W {
+        &mut self.out
+    }
+}
+
+impl<W: Write> Terminal for AnsiTerminal<W> {
+    fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    // Also hides the cursor, which may have been turned back on by anything else writing to the port
+    fn clear(&mut self) {
+        self.buffer += "\x1b[0m\x1b[2J\x1b[H\x1b[?25l";
+        self.colors = None;
+    }
+
+    fn write(&mut self, x: usize, y: usize, text: &str, fg: Color, bg: Color) {
+        let _ = write!(self.buffer, "\x1b[{};{}H", y + 1, x + 1);
+        if self.colors != Some((fg, bg)) {
+            let _ = write!(self.buffer, "\x1b[{};{}m", code(fg), code(bg) + 10);
+            self.colors = Some((fg, bg));
+        }
+        self.buffer += text;
+    }
+
+    fn flush(&mut self) {
+        if !self.buffer.is_empty() {
+            let _ = self.out.write_str(&self.buffer);
+            self.buffer.clear();
+        }
+    }
+}
+
+// SGR foreground code, backgrounds are 10 higher
+fn code(color: Color) -> u32 {
+    match color {
+        Color::Black => 30,
+        Color::Red => 31,
+        Color::Green => 32,
+        Color::Brown => 33,
+        Color::Blue => 34,
+        Color::Magenta => 35,
+        Color::Cyan => 36,
+        Color::LightGray => 37,
+        Color::DarkGray => 90,
+        Color::LightRed => 91,
+        Color::LightGreen => 92,
+        Color::Yellow => 93,
+        Color::LightBlue => 94,
+        Color::LightMagenta => 95,
+        Color::LightCyan => 96,
+        Color::White => 97,
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum State {
+    Ground,
+    Escape,   // got ESC
+    Sequence, // got ESC [ or ESC O, waiting for the final byte
+}
+
+// Turns the bytes a terminal sends into key codes
+pub struct Decoder {
+    state: State,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self {
+            state: State::Ground,
+        }
+    }
+}
+
+impl Decoder {
+    pub fn feed(&mut self, byte: u8) -> Option<KeyCode> {
+        match (self.state, byte) {
+            (State::Ground, 0x1b) => {
+                self.state = State::Escape;
+                None
+            }
+            (State::Ground, b'\r') | (State::Ground, b'\n') => Some(KeyCode::Char('\r')),
+            (State::Ground, byte) if byte.is_ascii_graphic() || byte == b' ' => {
+                Some(KeyCode::Char(byte as char))
+            }
+            (State::Ground, _) => None,
+            (State::Escape, b'[') | (State::Escape, b'O') => {
+                self.state = State::Sequence;
+                None
+            }
+            // Escape pressed twice
+            (State::Escape, 0x1b) => {
+                self.state = State::Ground;
+                Some(KeyCode::Escape)
+            }
+            // Alt+key, the key on its own
+            (State::Escape, byte) => {
+                self.state = State::Ground;
+                self.feed(byte)
+            }
+            (State::Sequence, byte) => {
+                // Parameters and intermediates come before the final byte
+                if !(0x40..=0x7e).contains(&byte) {
+                    return None;
+                }
+                self.state = State::Ground;
+                match byte {
+                    b'A' => Some(KeyCode::Up),
+                    b'B' => Some(KeyCode::Down),
+                    b'C' => Some(KeyCode::Right),
+                    b'D' => Some(KeyCode::Left),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    // A lone ESC only means the escape key once nothing else follows it
+    pub fn idle(&mut self) -> Option<KeyCode> {
+        match self.state {
+            State::Escape => {
+                self.state = State::Ground;
+                Some(KeyCode::Escape)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    // Keys decoded from `bytes`, with `idle` once they are all read
+    fn keys(bytes: &[u8]) -> Vec<KeyCode> {
+        let mut decoder = Decoder::default();
+        let mut keys: Vec<KeyCode> = bytes.iter().filter_map(|b| decoder.feed(*b)).collect();
+        keys.extend(decoder.idle());
+        keys
+    }
+
+    #[test]
+    fn arrow_keys() {
+        let csi = keys(b"\x1b[A\x1b[B\x1b[C\x1b[D");
+        let ss3 = keys(b"\x1bOA\x1bOB\x1bOC\x1bOD");
+        let arrows = [KeyCode::Up, KeyCode::Down, KeyCode::Right, KeyCode::Left];
+        assert_eq!(csi, arrows);
+        assert_eq!(ss3, arrows);
+        // Parameters like a modifier are skipped, unknown sequences give nothing
+        assert_eq!(
+            keys(b"\x1b[1;5A\x1b[3~x"),
+            [KeyCode::Up, KeyCode::Char('x')]
+        );
+    }
+
+    #[test]
+    fn double_escape() {
+        let mut decoder = Decoder::default();
+        assert_eq!(decoder.feed(0x1b), None);
+        assert_eq!(decoder.feed(0x1b), Some(KeyCode::Escape));
+        assert_eq!(decoder.idle(), None);
+        assert_eq!(decoder.feed(b'['), Some(KeyCode::Char('[')));
+    }
+
+    #[test]
+    fn alt_key() {
+        assert_eq!(keys(b"\x1bq"), [KeyCode::Char('q')]);
+        assert_eq!(keys(b"\x1b\r"), [KeyCode::Char('\r')]);
+    }
+
+    #[test]
+    fn lone_escape_on_idle() {
+        let mut decoder = Decoder::default();
+        assert_eq!(decoder.idle(), None);
+        assert_eq!(decoder.feed(0x1b), None);
+        assert_eq!(decoder.idle(), Some(KeyCode::Escape));
+        assert_eq!(decoder.idle(), None);
+        assert_eq!(decoder.feed(b'A'), Some(KeyCode::Char('A')));
+    }
+}
@@ -0,0 +1,278 @@
+use crate::game::{GameData, GameState, Matrix, Tetrimino};
+use crate::garbage;
+use crate::replay::{self, Input};
+use crate::settings::{Action, Difficulty};
+use alloc::collections::VecDeque;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Evaluation weights, per cell or line
+const HEIGHT: i32 = -5;
+const HOLE: i32 = -40;
+const BUMPINESS: i32 = -4;
+const HIGH_STACK: i32 = -20; // for every row above HIGH_ROWS in the highest column
+const HIGH_ROWS: i32 = 10;
+const LINE: i32 = 10;
+const ATTACK: i32 = 40;
+const T_SLOT: i32 = 25;
+
+// Columns of the matrix the pieces can reach
+const COLUMNS: core::ops::Range<usize> = 4..14;
+// Enough soft drops to reach the floor from the spawn position
+const SOFT_DROPS: usize = 22;
+// Placements tried per call, a deeper search is spread over several frames
+const SEARCH_STEP: usize = 100;
+
+// Time for a whole placement in ms, and how many pieces it looks at
+fn limits(difficulty: Difficulty) -> (u64, usize) {
+    match difficulty {
+        Difficulty::Easy => (1250, 1),
+        Difficulty::Normal => (650, 1),
+        Difficulty::Hard => (300, 2),
+    }
+}
+
+// Plays a board through the same actions a player has, spread out over the time it gets per piece
+pub struct Bot {
+    difficulty: Difficulty,
+    plan: VecDeque<Action>,
+    planned: Option<u32>,   // pieces locked when the plan was made
+    search: Option<Search>, // the active piece's, while it runs
+    step: u64,              // ms between actions
+    next_at: u64,
+}
+
+impl Bot {
+    pub fn new(difficulty: Difficulty) -> Self {
+        Self {
+            difficulty,
+            plan: VecDeque::new(),
+            planned: None,
+            search: None,
+            step: 0,
+            next_at: 0,
+        }
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    // The next action once its time has come, plans a placement for every new piece
+    pub fn next(&mut self, data: &GameData, now: u64) -> Option<Action> {
+        if data.state != GameState::Drop {
+            return None;
+        }
+        if self.planned != Some(data.stats.pieces) {
+            let (time, depth) = limits(self.difficulty);
+            // A piece that locked before its search was done gets a new one
+            match self.search.as_mut() {
+                Some(search) if search.pieces == data.stats.pieces => {
+                    if !search.advance(SEARCH_STEP) {
+                        return None;
+                    }
+                }
+                _ => {
+                    self.search = Some(Search::new(data, depth));
+                    return None;
+                }
+            }
+            self.plan = self.search.take().unwrap().best.1.into();
+            self.planned = Some(data.stats.pieces);
+            self.step = time / self.plan.len().max(1) as u64;
+            self.next_at = now + self.step;
+        }
+        if now < self.next_at {
+            return None;
+        }
+        self.next_at = now + self.step;
+        self.plan.pop_front()
+    }
+}
+
+// Every placement reachable by rotating and shifting at the top, then dropping,
+// T pieces also try a last rotation on the ground to find spins
+fn paths(data: &GameData) -> Vec<Vec<Action>> {
+    let tetrimino = data.active_mino.0;
+    let mut paths = Vec::new();
+    for rotation in 0..4 {
+        let turn = match rotation {
+            0 => vec![],
+            1 => vec![Action::RotateCw],
+            2 => vec![Action::RotateCw, Action::RotateCw],
+            _ => vec![Action::RotateCcw],
+        };
+        for shift in -5..=5_i32 {
+            let mut path = turn.clone();
+            let action = if shift < 0 {
+                Action::Left
+            } else {
+                Action::Right
+            };
+            path.extend((0..shift.abs()).map(|_| action));
+            let mut drop = path.clone();
+            drop.push(Action::HardDrop);
+            paths.push(drop);
+            if tetrimino == Tetrimino::T {
+                for spin in [Action::RotateCw, Action::RotateCcw].iter() {
+                    let mut path = path.clone();
+                    // Soft drops keep the piece where a last rotation can still kick it in
+                    path.extend((0..SOFT_DROPS).map(|_| Action::SoftDrop));
+                    path.push(*spin);
+                    path.push(Action::HardDrop);
+                    paths.push(path);
+                }
+            }
+        }
+    }
+    paths
+}
+
+// The search for the active piece's placement, a few placements at a time
+struct Search {
+    pieces: u32, // pieces locked when it started
+    depth: usize,
+    placements: Vec<Placement>,
+    scored: usize,
+    best: (i32, Vec<Action>),
+}
+
+impl Search {
+    fn new(data: &GameData, depth: usize) -> Self {
+        Self {
+            pieces: data.stats.pieces,
+            depth,
+            placements: placements(data, true, &mut 0),
+            scored: 0,
+            best: (i32::MIN, vec![Action::HardDrop]),
+        }
+    }
+
+    // Scores placements until `budget` boards have been tried, returns whether all are scored
+    fn advance(&mut self, budget: usize) -> bool {
+        let mut nodes = 0;
+        while nodes < budget {
+            let (board, actions, score) = match self.placements.get(self.scored) {
+                Some(placement) => placement,
+                None => break,
+            };
+            let score = score + rest(board, self.depth, &mut nodes);
+            if score > self.best.0 {
+                self.best = (score, actions.clone());
+            }
+            self.scored += 1;
+        }
+        self.scored == self.placements.len()
+    }
+}
+
+// The board after a piece locked, the actions that got it there and the score for what it cleared
+type Placement = (GameData, Vec<Action>, i32);
+
+// Every placement that doesn't top out, only the first piece also tries hold to keep the search short.
+// `nodes` counts the boards tried
+fn placements(data: &GameData, hold: bool, nodes: &mut usize) -> Vec<Placement> {
+    let mut placements = Vec::new();
+    let mut starts = vec![(data.clone(), Vec::new())];
+    if hold && !data.hold_flag {
+        let mut held = data.clone();
+        replay::apply(&mut held, Input::Action(Action::Hold));
+        starts.push((held, vec![Action::Hold]));
+    }
+
+    for (start, prefix) in starts {
+        let mut seen: Vec<Vec<(usize, usize)>> = Vec::new();
+        for path in paths(&start) {
+            *nodes += 1;
+            let mut board = start.clone();
+            let mut moved = prefix.clone();
+            for action in path.iter() {
+                let before = board.active_mino;
+                replay::apply(&mut board, Input::Action(*action));
+                // Soft drops past the floor change nothing and would only slow the bot down
+                if board.active_mino != before || *action == Action::HardDrop {
+                    moved.push(*action);
+                }
+            }
+            // Different paths often end in the same cells
+            let (tetrimino, x, y, rot) = board.active_mino;
+            let mut cells = tetrimino.cells((x, y), rot);
+            cells.sort_unstable();
+            if seen.contains(&cells) {
+                continue;
+            }
+            seen.push(cells);
+
+            let clear = match replay::apply(&mut board, Input::Lock) {
+                Some(clear) if board.state == GameState::Drop => clear,
+                _ => continue, // topped out
+            };
+            let score = clear.lines as i32 * LINE + garbage::attack(&clear) as i32 * ATTACK;
+            placements.push((board, moved, score));
+        }
+    }
+    placements
+}
+
+// Score of a board a piece was just placed on, looking `depth - 1` pieces further
+fn rest(board: &GameData, depth: usize, nodes: &mut usize) -> i32 {
+    if depth > 1 {
+        search(board, depth - 1, nodes)
+    } else {
+        evaluate(&board.matrix)
+    }
+}
+
+// Best score for the active piece and the `depth - 1` after it
+fn search(data: &GameData, depth: usize, nodes: &mut usize) -> i32 {
+    placements(data, false, nodes)
+        .iter()
+        .map(|(board, _, score)| score + rest(board, depth, nodes))
+        .max()
+        .unwrap_or(i32::MIN)
+}
+
+// Flat, low and without holes is good, a place for the next T-spin is better
+fn evaluate(matrix: &Matrix) -> i32 {
+    let heights: Vec<i32> = COLUMNS
+        .map(|x| {
+            (4..matrix.len())
+                .rev()
+                .find(|y| matrix[*y][x].is_some())
+                .map_or(0, |y| y as i32 - 3)
+        })
+        .collect();
+    let holes: i32 = COLUMNS
+        .zip(heights.iter())
+        .map(|(x, height)| {
+            (4..4 + *height as usize)
+                .filter(|y| matrix[*y][x].is_none())
+                .count() as i32
+        })
+        .sum();
+    let bumpiness: i32 = heights.windows(2).map(|w| (w[0] - w[1]).abs()).sum();
+    let highest = *heights.iter().max().unwrap();
+
+    heights.iter().sum::<i32>() * HEIGHT
+        + holes * HOLE
+        + bumpiness * BUMPINESS
+        + (highest - HIGH_ROWS).max(0) * HIGH_STACK
+        + t_slots(matrix) * T_SLOT
+}
+
+// A T-spin double setup: a notch in the floor, an open row above it and an overhang on one side
+fn t_slots(matrix: &Matrix) -> i32 {
+    let filled = |x: usize, y: usize| matrix[y][x].is_some();
+    let mut slots = 0;
+    for y in 4..matrix.len() - 3 {
+        for x in COLUMNS.start + 1..COLUMNS.end - 1 {
+            let notch = !filled(x, y) && filled(x - 1, y) && filled(x + 1, y);
+            let open = (x - 1..=x + 1).all(|x| !filled(x, y + 1)) && !filled(x, y + 2);
+            let overhang = filled(x - 1, y + 2) != filled(x + 1, y + 2);
+            if notch && open && overhang {
+                slots += 1;
+            }
+        }
+    }
+    slots
+}
@@ -0,0 +1,105 @@
+use crate::ui::{Screen, HEIGHT, WIDTH};
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::*;
+
+// In-memory screen for rendering without any graphics hardware
+#[derive(Clone, PartialEq)]
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<Rgb888>,
+}
+
+impl Default for Canvas {
+    fn default() -> Self {
+        Self::new(WIDTH, HEIGHT)
+    }
+}
+
+impl DrawTarget<Rgb888> for Canvas {
+    type Error = ();
+
+    fn draw_pixel(&mut self, item: Pixel<Rgb888>) -> Result<(), Self::Error> {
+        let Pixel(coord, color) = item;
+        if (0..self.width as i32).contains(&coord.x) && (0..self.height as i32).contains(&coord.y) {
+            self.pixels[(coord.y as u32 * self.width + coord.x as u32) as usize] = color;
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    fn clear(&mut self, color: Rgb888) -> Result<(), Self::Error> {
+        self.pixels = vec![color; (self.width * self.height) as usize];
+        Ok(())
+    }
+}
+
+impl Screen for Canvas {
+    fn present(&mut self) {}
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Rgb888::BLACK; (width * height) as usize],
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Rgb888 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    // Binary (P6) PPM
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut out = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for pixel in self.pixels.iter() {
+            out.extend_from_slice(&[pixel.r(), pixel.g(), pixel.b()]);
+        }
+        out
+    }
+
+    // Only reads what `to_ppm` writes: P6, 8 bit, no comments
+    pub fn from_ppm(data: &[u8]) -> Option<Self> {
+        let mut fields = data.splitn(5, |b| b.is_ascii_whitespace());
+        if fields.next()? != b"P6" {
+            return None;
+        }
+        let mut number =
+            || -> Option<u32> { core::str::from_utf8(fields.next()?).ok()?.parse().ok() };
+        let (width, height, depth) = (number()?, number()?, number()?);
+        let pixels = fields.next()?;
+        if depth != 255 || pixels.len() != (width * height * 3) as usize {
+            return None;
+        }
+
+        let pixels = pixels
+            .chunks(3)
+            .map(|p| Rgb888::new(p[0], p[1], p[2]))
+            .collect();
+        Some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    // Number of pixels that differ between both canvases, all of them if the sizes differ
+    pub fn diff(&self, other: &Canvas) -> usize {
+        if (self.width, self.height) != (other.width, other.height) {
+            return self.pixels.len().max(other.pixels.len());
+        }
+        self.pixels
+            .iter()
+            .zip(other.pixels.iter())
+            .filter(|(a, b)| a != b)
+            .count()
+    }
+}
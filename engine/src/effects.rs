@@ -0,0 +1,69 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Durations in ms
+pub const LINE_CLEAR_DELAY: u64 = 400;
+pub const LOCK_FLASH: u64 = 100;
+pub const TRAIL: u64 = 150;
+pub const POPUP: u64 = 1200;
+// Time between two redraws while anything is animating
+pub const FRAME: u64 = 16;
+
+pub enum Effect {
+    ClearRows(Vec<usize>),          // matrix rows flashing, then dissolving
+    LockFlash(Vec<(usize, usize)>), // matrix cells of the piece that just locked
+    Trail {
+        columns: Vec<usize>,
+        top: usize, // matrix rows, top is exclusive
+        bottom: usize,
+    },
+    Popup(Vec<String>),
+}
+
+// An effect together with the time it started and ends at
+pub struct Active {
+    pub effect: Effect,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Active {
+    // How far the effect has progressed, 0 to 255
+    pub fn progress(&self, now: u64) -> u32 {
+        ((now.min(self.end) - self.start) * 255 / (self.end - self.start).max(1)) as u32
+    }
+}
+
+#[derive(Default)]
+pub struct Effects {
+    active: Vec<Active>,
+}
+
+impl Effects {
+    pub fn add(&mut self, effect: Effect, now: u64, duration: u64) {
+        self.active.push(Active {
+            effect,
+            start: now,
+            end: now + duration,
+        });
+    }
+
+    // Drops finished effects, returns whether any did
+    pub fn update(&mut self, now: u64) -> bool {
+        let count = self.active.len();
+        self.active.retain(|a| a.end > now);
+        self.active.len() != count
+    }
+
+    pub fn clear(&mut self) {
+        self.active.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Active> {
+        self.active.iter()
+    }
+}
@@ -0,0 +1,68 @@
+use crate::game::{GameData, GameState, Tetrimino};
+use crate::replay::{self, Input};
+use crate::settings::Action;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Finesse is getting a piece where it goes with as few key presses as possible. Without auto repeat
+// every shift is a key press of its own, so the fewest are found by trying every rotation and shift
+// from the spawn position, like the bot does
+
+// Row the pieces drop to right after spawning
+const SPAWN_ROW: usize = 24;
+
+// Whether the active piece took more shifts and rotations than it needed to get where it is.
+// Placements a straight drop can't reach, tucks and spins, are never faults
+pub fn fault(data: &GameData) -> bool {
+    let (tetrimino, x, y, rot) = data.active_mino;
+    let dropped = (y..=SPAWN_ROW).all(|y| !tetrimino.collides(&data.matrix, (x, y), rot));
+    dropped && matches!(minimum(tetrimino, x, rot), Some(min) if data.moves > min)
+}
+
+// Fewest shifts and rotations that land the piece in the same cells on an empty board
+fn minimum(tetrimino: Tetrimino, x: usize, rot: u8) -> Option<u32> {
+    let mut empty = GameData::new(0);
+    empty.active_mino = (tetrimino, x, SPAWN_ROW, rot);
+    let target = landing(&empty);
+
+    tetrimino.spawn(&mut empty);
+    empty.state = GameState::Drop;
+    let mut best = None;
+    for turn in [
+        vec![],
+        vec![Action::RotateCw],
+        vec![Action::RotateCw; 2],
+        vec![Action::RotateCcw],
+    ]
+    .iter()
+    {
+        for shift in -5..=5_i32 {
+            let action = if shift < 0 {
+                Action::Left
+            } else {
+                Action::Right
+            };
+            let path: Vec<Action> = turn
+                .iter()
+                .copied()
+                .chain((0..shift.abs()).map(|_| action))
+                .collect();
+            let mut data = empty.clone();
+            for action in path.iter() {
+                replay::apply(&mut data, Input::Action(*action));
+            }
+            if landing(&data) == target {
+                best = Some(best.map_or(path.len(), |best: usize| best.min(path.len())));
+            }
+        }
+    }
+    best.map(|best| best as u32)
+}
+
+// Cells of the active piece after a hard drop
+fn landing(data: &GameData) -> Vec<(usize, usize)> {
+    let (tetrimino, _, _, rot) = data.active_mino;
+    let mut cells = tetrimino.cells(data.ghost(), rot);
+    cells.sort_unstable();
+    cells
+}
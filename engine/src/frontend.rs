@@ -0,0 +1,49 @@
+use crate::effects::Effects;
+use crate::game::{GameData, Tetrimino};
+use crate::settings::{Renderer, Settings};
+use crate::sprites::Skin;
+use crate::stats::Stats;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Everything the game draws, implemented by the graphical and the text UI
+pub trait Frontend {
+    // Has to be called again when the screen size changes, returns whether anything moved
+    fn set_layout(&mut self, settings: &Settings, players: u32) -> bool;
+    fn draw_background(&mut self);
+    fn draw_board(&mut self, board: usize, data: &GameData);
+    fn draw_queue(&mut self, board: usize, queue: &VecDeque<Tetrimino>);
+    fn draw_hold(&mut self, board: usize, content: &Option<Tetrimino>, locked: bool);
+    fn draw_stats(&mut self, board: usize, stats: &Stats);
+    // Incoming garbage in versus
+    fn draw_garbage(&mut self, board: usize, lines: u32);
+    fn draw_timer(&mut self, board: usize, time: u64);
+    fn draw_effects(&mut self, board: usize, effects: &Effects, now: u64);
+    fn draw_messages(&mut self, title: &str, lines: &[String]);
+    fn draw_menu(&mut self, title: &str, items: &[(String, String)], selected: usize, hint: &str);
+    fn refresh(&mut self);
+
+    // Skins only apply to graphical frontends
+    fn set_skin(&mut self, _skin: Skin) {}
+
+    // Video mode control, if the frontend draws to a mode-switchable display
+    fn video(&mut self) -> Option<&mut dyn Video> {
+        None
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct VideoMode {
+    pub resolution: (usize, usize),
+    pub format: &'static str,
+}
+
+pub trait Video {
+    fn modes(&self) -> Vec<VideoMode>;
+    fn current_mode(&self) -> Option<usize>;
+    fn set_mode(&mut self, index: usize);
+    fn set_renderer(&mut self, renderer: Renderer);
+    // TSC cycles per full frame for the Blt and (if supported) the linear renderer
+    fn benchmark(&mut self, frames: u64) -> (u64, Option<u64>);
+}
@@ -0,0 +1,679 @@
+use crate::game::{GameData, GameState, Spin, Tetrimino};
+use crate::tbp::{self, Move};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Fumen (v115) board diagrams as shared on https://fumen.zui.jp and most Tetris communities.
+// Every page is a field diff against the page before, run-length encoded, then the piece on it
+// and an optional comment, all written as little-endian base 64 digits.
+
+const DIGITS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+// Comments use printable ASCII, 4 characters to 5 digits
+const COMMENT_CHARS: u32 = 96;
+const MAX_COMMENT: usize = 4095;
+
+const WIDTH: usize = 10;
+// Rows of the field, plus one below them for rising garbage
+const HEIGHT: usize = 23;
+const BLOCKS: usize = WIDTH * (HEIGHT + 1);
+
+// Rows bottom first
+pub type Field = [[Option<Tetrimino>; WIDTH]; HEIGHT];
+
+// A piece as fumen places it, `x` and `y` are its rotation center like in the bot protocol
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Piece {
+    pub tetrimino: Tetrimino,
+    pub rot: u8, // 0 north, clockwise from there like the engine
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Page {
+    pub field: Field,
+    pub piece: Option<Piece>,
+    pub comment: String, // carries over from the page before unless replaced
+}
+
+impl Page {
+    // The board of a game with the active piece where it is, and the hold piece, the active piece
+    // and the first `next` pieces of the queue as a quiz comment
+    pub fn from_game(data: &GameData, next: usize) -> Self {
+        let mut field: Field = [[None; WIDTH]; HEIGHT];
+        for (y, row) in field.iter_mut().enumerate() {
+            row.copy_from_slice(&data.matrix[4 + y][4..14]);
+        }
+        // Between a lock and the next spawn the active piece is the one that just locked
+        let (tetrimino, x, y, rot) = data.active_mino;
+        let piece = match data.state {
+            GameState::Drop | GameState::Settings => {
+                let (x, y) = tbp::center(tetrimino, (x, y), rot);
+                Some(Piece {
+                    tetrimino,
+                    rot,
+                    x,
+                    y,
+                })
+            }
+            _ => None,
+        };
+        let name = |piece: Option<&Tetrimino>| piece.map_or("", |piece| tbp::piece_name(*piece));
+        let mut queue = data.queue.iter();
+        let current = match piece {
+            Some(piece) => tbp::piece_name(piece.tetrimino),
+            None => name(queue.next()),
+        };
+        let next: String = queue.take(next).map(|piece| name(Some(piece))).collect();
+        Self {
+            field,
+            piece,
+            comment: format!("#Q=[{}]({}){}", name(data.hold.as_ref()), current, next),
+        }
+    }
+
+    // An endless game on the page, the pieces of its quiz comment come before the usual 7-bags.
+    // The piece on the page is the active one if it fits there, otherwise the first piece still has to spawn
+    pub fn game(&self, seed: u64) -> GameData {
+        let mut data = GameData::new(seed);
+        for (y, row) in self.field.iter().enumerate() {
+            data.matrix[4 + y][4..14].copy_from_slice(row);
+        }
+        let (hold, pieces) = quiz(&self.comment).unwrap_or_default();
+        data.hold = hold;
+        for piece in pieces.iter().rev() {
+            data.queue.push_front(*piece);
+        }
+
+        let active = self.piece.and_then(|piece| {
+            let mv = Move {
+                piece: piece.tetrimino,
+                rot: piece.rot,
+                x: piece.x,
+                y: piece.y,
+                spin: Spin::None,
+            };
+            tbp::position(&mv)
+                .filter(|pos| !piece.tetrimino.collides(&data.matrix, *pos, piece.rot))
+                .map(|pos| (piece, pos))
+        });
+        if let Some((piece, (x, y))) = active {
+            if data.queue.front() == Some(&piece.tetrimino) {
+                data.queue.pop_front();
+            }
+            data.active_mino = (piece.tetrimino, x, y, piece.rot);
+            data.state = GameState::Drop;
+        }
+        data
+    }
+}
+
+// Fields with the garbage row, as block numbers: 0 empty, then I L O Z T J S and 8 for garbage
+type Grid = [[u32; WIDTH]; HEIGHT + 1];
+
+fn block(tetrimino: Option<Tetrimino>) -> u32 {
+    match tetrimino {
+        None | Some(Tetrimino::Ghost) => 0,
+        Some(Tetrimino::I) => 1,
+        Some(Tetrimino::L) => 2,
+        Some(Tetrimino::O) => 3,
+        Some(Tetrimino::Z) => 4,
+        Some(Tetrimino::T) => 5,
+        Some(Tetrimino::J) => 6,
+        Some(Tetrimino::S) => 7,
+        Some(Tetrimino::Garbage) => 8,
+    }
+}
+
+fn tetrimino(block: u32) -> Option<Tetrimino> {
+    match block {
+        1 => Some(Tetrimino::I),
+        2 => Some(Tetrimino::L),
+        3 => Some(Tetrimino::O),
+        4 => Some(Tetrimino::Z),
+        5 => Some(Tetrimino::T),
+        6 => Some(Tetrimino::J),
+        7 => Some(Tetrimino::S),
+        8 => Some(Tetrimino::Garbage),
+        _ => None,
+    }
+}
+
+// Fumen counts rotations as south, east, north, west, this goes both ways
+fn rotation(rot: u32) -> u8 {
+    [2, 1, 0, 3][rot as usize % 4]
+}
+
+// The grid row and column of run-length index `idx`, fumen goes top to bottom and ends with the garbage row
+fn cell(idx: usize) -> (usize, usize) {
+    (HEIGHT - idx / WIDTH, idx % WIDTH)
+}
+
+// Piece positions are stored as the block the old fumen rotation center is on
+fn center(tetrimino: Tetrimino, rot: u8, x: i32, y: i32) -> (i32, i32) {
+    match (tetrimino, rot) {
+        (Tetrimino::O, 3) => (x + 1, y - 1),
+        (Tetrimino::O, 2) | (Tetrimino::I, 2) => (x + 1, y),
+        (Tetrimino::O, 0) => (x, y - 1),
+        (Tetrimino::I, 3) => (x, y - 1),
+        (Tetrimino::S, 0) | (Tetrimino::Z, 0) => (x, y - 1),
+        (Tetrimino::S, 1) => (x - 1, y),
+        (Tetrimino::Z, 3) => (x + 1, y),
+        _ => (x, y),
+    }
+}
+
+// The other way around, `None` for pieces that are not on the field
+fn stored(piece: &Piece) -> Option<(usize, usize)> {
+    let (x, y) = match (piece.tetrimino, piece.rot) {
+        (Tetrimino::O, 3) => (piece.x - 1, piece.y + 1),
+        (Tetrimino::O, 2) | (Tetrimino::I, 2) => (piece.x - 1, piece.y),
+        (Tetrimino::O, 0) => (piece.x, piece.y + 1),
+        (Tetrimino::I, 3) => (piece.x, piece.y + 1),
+        (Tetrimino::S, 0) | (Tetrimino::Z, 0) => (piece.x, piece.y + 1),
+        (Tetrimino::S, 1) => (piece.x + 1, piece.y),
+        (Tetrimino::Z, 3) => (piece.x - 1, piece.y),
+        _ => (piece.x, piece.y),
+    };
+    if (0..WIDTH as i32).contains(&x) && (0..HEIGHT as i32).contains(&y) {
+        Some((x as usize, y as usize))
+    } else {
+        None
+    }
+}
+
+// Appends `value` as `len` digits
+fn write(digits: &mut Vec<u32>, mut value: u32, len: usize) {
+    for _ in 0..len {
+        digits.push(value % 64);
+        value /= 64;
+    }
+}
+
+struct Reader {
+    digits: Vec<u32>,
+    pos: usize,
+}
+
+impl Reader {
+    fn done(&self) -> bool {
+        self.pos >= self.digits.len()
+    }
+
+    // A number of `len` digits
+    fn read(&mut self, len: usize) -> Result<u32, String> {
+        let digits = self
+            .digits
+            .get(self.pos..self.pos + len)
+            .ok_or("fumen ends early")?;
+        self.pos += len;
+        Ok(digits
+            .iter()
+            .rev()
+            .fold(0, |value, digit| value * 64 + digit))
+    }
+}
+
+// Every page of a fumen, given on its own or as the end of a fumen URL
+pub fn decode(fumen: &str) -> Result<Vec<Page>, String> {
+    let start = fumen.find("115@").ok_or("not a v115 fumen")?;
+    let digits = fumen[start + 4..]
+        .trim()
+        .bytes()
+        .filter(|c| *c != b'?')
+        .map(|c| DIGITS.iter().position(|d| *d == c).map(|d| d as u32))
+        .collect::<Option<Vec<u32>>>()
+        .ok_or("invalid character in fumen")?;
+    let mut reader = Reader { digits, pos: 0 };
+
+    let mut pages = Vec::new();
+    let mut grid: Grid = [[0; WIDTH]; HEIGHT + 1];
+    let mut comment = String::new();
+    // Pages left that repeat the field before them without a diff of their own
+    let mut repeat = 0;
+    while !reader.done() {
+        if repeat > 0 {
+            repeat -= 1;
+        } else {
+            let mut idx = 0;
+            while idx < BLOCKS {
+                let value = reader.read(2)?;
+                let (diff, run) = (value / BLOCKS as u32, value as usize % BLOCKS + 1);
+                if diff == 8 && run == BLOCKS {
+                    repeat = reader.read(1)?;
+                }
+                if idx + run > BLOCKS || diff > 16 {
+                    return Err(String::from("invalid field in fumen"));
+                }
+                for idx in idx..idx + run {
+                    let (row, col) = cell(idx);
+                    let block = (grid[row][col] + diff)
+                        .checked_sub(8)
+                        .filter(|block| *block <= 8)
+                        .ok_or("invalid field in fumen")?;
+                    grid[row][col] = block;
+                }
+                idx += run;
+            }
+        }
+
+        let mut action = reader.read(3)?;
+        let mut take = |count: u32| {
+            let value = action % count;
+            action /= count;
+            value
+        };
+        let kind = take(8);
+        let rot = rotation(take(4));
+        let coordinate = take(BLOCKS as u32) as usize;
+        let rise = take(2) == 1;
+        let mirror = take(2) == 1;
+        let _colors = take(2);
+        let has_comment = take(2) == 1;
+        let lock = take(2) == 0;
+
+        if has_comment {
+            let len = reader.read(2)? as usize;
+            let mut escaped = String::new();
+            for _ in 0..len.div_ceil(4) {
+                let mut value = reader.read(5)?;
+                for _ in 0..4 {
+                    escaped.push((b' ' + (value % COMMENT_CHARS) as u8) as char);
+                    value /= COMMENT_CHARS;
+                }
+            }
+            escaped.truncate(len.min(MAX_COMMENT));
+            comment = unescape(&escaped);
+        }
+
+        let piece = tetrimino(kind)
+            .filter(|tetrimino| *tetrimino != Tetrimino::Garbage)
+            .map(|tetrimino| {
+                let (row, col) = cell(coordinate);
+                let (x, y) = center(tetrimino, rot, col as i32, row as i32 - 1);
+                Piece {
+                    tetrimino,
+                    rot,
+                    x,
+                    y,
+                }
+            });
+        let mut field: Field = [[None; WIDTH]; HEIGHT];
+        for (y, row) in field.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                *cell = tetrimino(grid[y + 1][x]);
+            }
+        }
+        pages.push(Page {
+            field,
+            piece,
+            comment: comment.clone(),
+        });
+
+        if lock {
+            if let Some(piece) = piece {
+                place(&mut grid, &piece);
+            }
+            clear_lines(&mut grid);
+            if rise {
+                for y in (2..=HEIGHT).rev() {
+                    grid[y] = grid[y - 1];
+                }
+                grid[1] = grid[0];
+                grid[0] = [0; WIDTH];
+            }
+            if mirror {
+                for row in grid[1..].iter_mut() {
+                    row.reverse();
+                }
+            }
+        }
+    }
+    if pages.is_empty() {
+        return Err(String::from("fumen has no pages"));
+    }
+    Ok(pages)
+}
+
+// Pages as a fumen string like fumen.zui.jp writes it, pieces lock at the end of their page like in the editor
+pub fn encode(pages: &[Page]) -> String {
+    // Every page's field and the one it is a diff against, which is the page before with its piece locked
+    let mut grids = Vec::new();
+    let mut grid: Grid = [[0; WIDTH]; HEIGHT + 1];
+    for page in pages.iter() {
+        let mut next: Grid = [[0; WIDTH]; HEIGHT + 1];
+        for (y, row) in page.field.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                next[y + 1][x] = block(*cell);
+            }
+        }
+        grids.push((grid, next));
+        grid = next;
+        if let Some(piece) = page.piece.filter(|piece| stored(piece).is_some()) {
+            place(&mut grid, &piece);
+        }
+        clear_lines(&mut grid);
+    }
+    let unchanged: Vec<bool> = grids
+        .iter()
+        .map(|(before, after)| before == after)
+        .collect();
+
+    let mut digits = Vec::new();
+    let mut comment = String::new();
+    let mut repeat = 0;
+    for (idx, page) in pages.iter().enumerate() {
+        if repeat > 0 {
+            repeat -= 1;
+        } else {
+            let (before, after) = &grids[idx];
+            let diffs: Vec<u32> = (0..BLOCKS)
+                .map(|idx| {
+                    let (row, col) = cell(idx);
+                    after[row][col] + 8 - before[row][col]
+                })
+                .collect();
+            let mut start = 0;
+            while start < BLOCKS {
+                let run = diffs[start..]
+                    .iter()
+                    .take_while(|diff| **diff == diffs[start])
+                    .count();
+                write(
+                    &mut digits,
+                    diffs[start] * BLOCKS as u32 + run as u32 - 1,
+                    2,
+                );
+                start += run;
+            }
+            // An unchanged field is followed by the number of pages after it that are unchanged as well
+            if unchanged[idx] {
+                repeat = unchanged[idx + 1..]
+                    .iter()
+                    .take(63)
+                    .take_while(|unchanged| **unchanged)
+                    .count();
+                write(&mut digits, repeat as u32, 1);
+            }
+        }
+
+        let (kind, rot, coordinate) =
+            match page.piece.and_then(|piece| Some((piece, stored(&piece)?))) {
+                Some((piece, (x, y))) => (
+                    block(Some(piece.tetrimino)),
+                    rotation(piece.rot as u32) as u32,
+                    (HEIGHT - 1 - y) * WIDTH + x,
+                ),
+                None => (0, 0, 0),
+            };
+        let has_comment = page.comment != comment;
+        // Same fields as in decode: no rise or mirror, piece colors on like the editor has them, then comment and lock
+        let fields = [
+            (kind, 8),
+            (rot, 4),
+            (coordinate as u32, BLOCKS as u32),
+            (0, 2),
+            (0, 2),
+            (1, 2),
+            (has_comment as u32, 2),
+            (0, 2),
+        ];
+        let action = fields
+            .iter()
+            .rev()
+            .fold(0, |action, (value, count)| action * count + value);
+        write(&mut digits, action, 3);
+
+        if has_comment {
+            let escaped = escape(&page.comment);
+            write(&mut digits, escaped.len() as u32, 2);
+            for chunk in escaped.as_bytes().chunks(4) {
+                let value = chunk
+                    .iter()
+                    .rev()
+                    .fold(0, |value, c| value * COMMENT_CHARS + (c - b' ') as u32);
+                write(&mut digits, value, 5);
+            }
+            comment = page.comment.clone();
+        }
+    }
+
+    let mut fumen = String::from("v115@");
+    for (idx, digit) in digits.iter().enumerate() {
+        // Long fumens are broken up with `?` after the first 42 digits and every 47 after them
+        if idx >= 42 && (idx - 42) % 47 == 0 {
+            fumen.push('?');
+        }
+        fumen.push(DIGITS[*digit as usize] as char);
+    }
+    fumen
+}
+
+// Cells off the field are left out
+fn place(grid: &mut Grid, piece: &Piece) {
+    for (dx, dy) in tbp::center_cells(piece.tetrimino, piece.rot).iter() {
+        let (x, y) = (piece.x + dx, piece.y + dy);
+        if (0..WIDTH as i32).contains(&x) && (0..HEIGHT as i32).contains(&y) {
+            grid[y as usize + 1][x as usize] = block(Some(piece.tetrimino));
+        }
+    }
+}
+
+fn clear_lines(grid: &mut Grid) {
+    let mut rows: Vec<[u32; WIDTH]> = grid[1..]
+        .iter()
+        .filter(|row| row.contains(&0))
+        .copied()
+        .collect();
+    rows.resize(HEIGHT, [0; WIDTH]);
+    grid[1..].copy_from_slice(&rows);
+}
+
+// JavaScript's escape(), which fumen runs on comments, cut off at the longest comment fumen takes
+fn escape(text: &str) -> String {
+    let mut out = String::new();
+    // Like JavaScript, characters outside the basic plane are escaped as two UTF-16 halves
+    for unit in text.encode_utf16() {
+        let escaped = match unit {
+            0x100..=0xffff => format!("%u{:04X}", unit),
+            _ => match unit as u8 {
+                c if c.is_ascii_alphanumeric() || b"@*_+-./".contains(&c) => {
+                    format!("{}", c as char)
+                }
+                c => format!("%{:02X}", c),
+            },
+        };
+        if out.len() + escaped.len() > MAX_COMMENT {
+            break;
+        }
+        out += &escaped;
+    }
+    out
+}
+
+// Undoes escape()
+fn unescape(text: &str) -> String {
+    let mut units = Vec::new();
+    let mut rest = text;
+    while let Some(pos) = rest.find('%') {
+        units.extend(rest[..pos].encode_utf16());
+        rest = &rest[pos + 1..];
+        // %XX for bytes, %uXXXX for everything else
+        let (skip, len) = if rest.starts_with('u') {
+            (1, 4)
+        } else {
+            (0, 2)
+        };
+        let code = rest
+            .get(skip..skip + len)
+            .and_then(|hex| u16::from_str_radix(hex, 16).ok());
+        match code {
+            Some(unit) => {
+                units.push(unit);
+                rest = &rest[skip + len..];
+            }
+            None => units.push(b'%' as u16),
+        }
+    }
+    units.extend(rest.encode_utf16());
+    String::from_utf16_lossy(&units)
+}
+
+// The pieces of a quiz comment, `#Q=[hold](current)next`, as the hold piece and the queue starting with the current piece
+pub fn quiz(comment: &str) -> Option<(Option<Tetrimino>, Vec<Tetrimino>)> {
+    let rest = comment.trim().strip_prefix("#Q=[")?;
+    let (hold, rest) = rest.split_at(rest.find(']')?);
+    let rest = rest[1..].strip_prefix('(')?;
+    let (current, rest) = rest.split_at(rest.find(')')?);
+    let hold = match hold {
+        "" => None,
+        name => Some(tbp::parse_piece(name)?),
+    };
+    let mut queue = Vec::new();
+    let pieces = current.chars().chain(rest[1..].chars());
+    for c in pieces.filter(|c| !c.is_whitespace()) {
+        queue.push(tbp::parse_piece(&format!("{}", c))?);
+    }
+    Some((hold, queue))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const PIECES: [Tetrimino; 7] = [
+        Tetrimino::O,
+        Tetrimino::I,
+        Tetrimino::T,
+        Tetrimino::L,
+        Tetrimino::J,
+        Tetrimino::S,
+        Tetrimino::Z,
+    ];
+
+    // A small stack so the field diff is not empty
+    fn board() -> GameData {
+        let mut data = GameData::new(3);
+        for (x, piece) in PIECES.iter().enumerate() {
+            data.matrix[4][4 + x] = Some(*piece);
+        }
+        data.hold = Some(Tetrimino::T);
+        data
+    }
+
+    // Every piece in every rotation, pushed against either wall and onto the floor, comes back
+    // where it was. The center offsets of O, I, S and Z are what keep them on the field there
+    #[test]
+    fn pieces_round_trip() {
+        for piece in PIECES.iter().copied() {
+            for rot in 0..4 {
+                let mut data = board();
+                let fits = |x: usize, y: usize| !piece.collides(&data.matrix, (x, y), rot);
+                let columns: Vec<usize> = (0..16).filter(|x| fits(*x, 20)).collect();
+                let floor = (0..30).find(|y| fits(columns[0], *y)).unwrap();
+                let positions = [
+                    (columns[0], 20),
+                    (columns[columns.len() - 1], 20),
+                    (columns[0], floor),
+                ];
+                for (x, y) in positions.iter().copied() {
+                    data.active_mino = (piece, x, y, rot);
+                    data.state = GameState::Drop;
+                    let fumen = encode(&[Page::from_game(&data, 5)]);
+                    let imported = decode(&fumen).unwrap()[0].game(3);
+                    let context =
+                        format!("{:?} rotation {} at {:?}: {}", piece, rot, (x, y), fumen);
+                    assert!(imported.matrix == data.matrix, "{}", context);
+                    assert_eq!(imported.active_mino, data.active_mino, "{}", context);
+                    assert!(imported.state == GameState::Drop, "{}", context);
+                    assert_eq!(imported.hold, data.hold, "{}", context);
+                    assert!(
+                        imported.queue.iter().take(5).eq(data.queue.iter().take(5)),
+                        "{}",
+                        context
+                    );
+                }
+            }
+        }
+    }
+
+    // The piece locks where the engine puts its cells, checked on a page with an empty field diff
+    #[test]
+    fn pieces_lock_into_next_page() {
+        for piece in PIECES.iter().copied() {
+            for rot in 0..4 {
+                let mut data = board();
+                data.active_mino = (piece, 8, 10, rot);
+                data.state = GameState::Drop;
+                let fumen = encode(&[Page::from_game(&data, 5)]) + "vhAAgH";
+                let pages = decode(&fumen).unwrap();
+                for (x, y) in piece.cells((8, 10), rot) {
+                    data.matrix[y][x] = Some(piece);
+                }
+                let locked = pages[1].game(3);
+                assert!(
+                    locked.matrix == data.matrix,
+                    "{:?} rotation {}: {}",
+                    piece,
+                    rot,
+                    fumen
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn quiz_comments() {
+        let mut data = board();
+        data.queue = [Tetrimino::I, Tetrimino::O, Tetrimino::S, Tetrimino::Z]
+            .iter()
+            .copied()
+            .collect();
+        data.state = GameState::Spawn;
+        let page = Page::from_game(&data, 3);
+        assert_eq!(page.comment, "#Q=[T](I)OSZ");
+        assert_eq!(
+            quiz(&page.comment),
+            Some((
+                Some(Tetrimino::T),
+                vec![Tetrimino::I, Tetrimino::O, Tetrimino::S, Tetrimino::Z]
+            ))
+        );
+        assert_eq!(
+            decode(&encode(core::slice::from_ref(&page))).unwrap(),
+            vec![page]
+        );
+
+        assert_eq!(
+            quiz("#Q=[](L)J"),
+            Some((None, vec![Tetrimino::L, Tetrimino::J]))
+        );
+        assert_eq!(quiz("no quiz"), None);
+    }
+
+    // Comments outside printable ASCII are escaped like JavaScript's `escape`
+    #[test]
+    fn comments_round_trip() {
+        let mut page = decode("v115@vhAAgH").unwrap().remove(0);
+        page.comment = String::from("héllo 😀 #Q=[T](I)OSZ");
+        let pages = vec![page.clone(), page.clone(), page];
+        assert_eq!(decode(&encode(&pages)).unwrap(), pages);
+    }
+
+    #[test]
+    fn line_breaks() {
+        let mut data = board();
+        data.state = GameState::Spawn;
+        let pages: Vec<Page> = (0..8).map(|next| Page::from_game(&data, next)).collect();
+        let fumen = encode(&pages);
+        let digits = &fumen["v115@".len()..];
+        let breaks: Vec<usize> = digits.match_indices('?').map(|(idx, _)| idx).collect();
+        assert!(breaks.len() >= 2, "{}", fumen);
+        for (n, idx) in breaks.iter().enumerate() {
+            assert_eq!(*idx, 42 + n * 48, "{}", fumen);
+        }
+        assert_eq!(decode(&fumen).unwrap(), pages);
+    }
+}
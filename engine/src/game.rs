@@ -0,0 +1,435 @@
+use crate::stats::Stats;
+use alloc::collections::vec_deque::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use rand::rngs::SmallRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+pub type Matrix = [[Option<Tetrimino>; 18]; 44];
+
+// Rows of the matrix, counted from the floor at 4
+pub const VISIBLE_ROWS: core::ops::Range<usize> = 4..24;
+pub const BUFFER_ROWS: core::ops::Range<usize> = 24..26;
+// Most pieces the next queue can show
+pub const PREVIEW_MAX: usize = 7;
+// Stack height (as a matrix row) below the spawn position that counts as dangerous
+const DANGER_ROW: usize = 20;
+
+#[derive(Clone)]
+pub struct GameData {
+    pub active_mino: (Tetrimino, usize, usize, u8), // type, x, y, rot
+    pub queue: VecDeque<Tetrimino>,                 // upcoming pieces, at least PREVIEW_MAX long
+    pub hold: Option<Tetrimino>,
+    pub hold_flag: bool,
+    pub state: GameState,
+    pub matrix: Matrix,
+    pub rng: SmallRng,
+    pub stats: Stats,
+    pub rotated: bool, // last successful move was a rotation
+    pub moves: u32,    // shifts and rotations pressed for the active piece, for finesse
+    pub combo: i32,    // -1 until two pieces in a row clear lines
+    pub back_to_back: bool,
+    pub endless: bool, // refills the queue from 7-bags, otherwise the game ends when it runs out
+}
+
+impl GameData {
+    pub fn new(seed: u64) -> Self {
+        let mut data = Self {
+            active_mino: (Tetrimino::O, 0, 0, 0),
+            queue: VecDeque::new(),
+            hold: None,
+            hold_flag: false,
+            state: GameState::Spawn,
+            matrix: [[None; 18]; 44], // + 4 buffer blocks on each side
+            rng: SmallRng::seed_from_u64(seed),
+            stats: Stats::default(),
+            rotated: false,
+            moves: 0,
+            combo: -1,
+            back_to_back: false,
+            endless: true,
+        };
+        data.fill_queue();
+        data
+    }
+
+    pub fn get_piece(&mut self) -> Tetrimino {
+        let piece = self.queue.pop_front().unwrap();
+        self.fill_queue();
+        piece
+    }
+
+    fn fill_queue(&mut self) {
+        while self.endless && self.queue.len() < PREVIEW_MAX {
+            self.push_bag();
+        }
+    }
+
+    // Whether the stack is getting close to the spawn position
+    pub fn danger(&self) -> bool {
+        self.matrix[DANGER_ROW..]
+            .iter()
+            .any(|row| row[6..12].iter().any(|e| e.is_some()))
+    }
+
+    // Lock out: the piece locked completely above the visible matrix
+    pub fn locked_out(&self) -> bool {
+        let (tetrimino, x, y, rot) = self.active_mino;
+        tetrimino
+            .cells((x, y), rot)
+            .iter()
+            .all(|(_, y)| *y >= VISIBLE_ROWS.end)
+    }
+
+    // Where the active piece would land
+    pub fn ghost(&self) -> (usize, usize) {
+        let (tetrimino, x, mut y, rot) = self.active_mino;
+        while !tetrimino.collides(&self.matrix, (x, y - 1), rot) {
+            y -= 1;
+        }
+        (x, y)
+    }
+
+    // Writes the active piece into the matrix, which unlocks hold again
+    pub fn lock(&mut self) {
+        self.hold_flag = false;
+        let (tetrimino, x, y, rot) = self.active_mino;
+        for (x, y) in tetrimino.cells((x, y), rot) {
+            self.matrix[y][x] = Some(tetrimino);
+        }
+    }
+
+    // Rows of the matrix without gaps, bottom to top
+    pub fn full_rows(&self) -> Vec<usize> {
+        (0..self.matrix.len())
+            .filter(|y| self.matrix[*y].iter().skip(4).take(10).all(|e| e.is_some()))
+            .collect()
+    }
+
+    pub fn clear_rows(&mut self, rows: &[usize]) {
+        // Top to bottom so the lower indices stay valid
+        for row in rows.iter().rev() {
+            for y in *row..self.matrix.len() - 1 {
+                self.matrix[y] = self.matrix[y + 1];
+            }
+            self.matrix[self.matrix.len() - 1] = [None; 18];
+        }
+    }
+
+    // Pushes the stack up by `lines` rows of garbage, open at column `hole` (0-9)
+    pub fn add_garbage(&mut self, lines: usize, hole: usize) {
+        let lines = lines.min(self.matrix.len() - 4);
+        for y in (4 + lines..self.matrix.len()).rev() {
+            self.matrix[y] = self.matrix[y - lines];
+        }
+        for y in 4..4 + lines {
+            for x in 4..14 {
+                self.matrix[y][x] = if x == 4 + hole {
+                    None
+                } else {
+                    Some(Tetrimino::Garbage)
+                };
+            }
+        }
+    }
+
+    // Classifies the lock of the active piece, which clears `rows`, and updates combo and back-to-back
+    pub fn score_clear(&mut self, rows: &[usize]) -> Clear {
+        let spin = self.spin();
+        let lines = rows.len();
+        let perfect = lines > 0
+            && self
+                .matrix
+                .iter()
+                .enumerate()
+                .all(|(y, row)| rows.contains(&y) || row.iter().all(|e| e.is_none()));
+
+        let mut back_to_back = false;
+        if lines > 0 {
+            self.combo += 1;
+            let difficult = lines == 4 || spin != Spin::None;
+            back_to_back = difficult && self.back_to_back;
+            self.back_to_back = difficult;
+        } else {
+            self.combo = -1;
+        }
+
+        Clear {
+            lines,
+            spin,
+            back_to_back,
+            combo: self.combo,
+            perfect,
+        }
+    }
+
+    // Three-corner rule, mini if only one of the corners the T points at is filled
+    fn spin(&self) -> Spin {
+        let (tetrimino, x, y, rot) = self.active_mino;
+        if tetrimino != Tetrimino::T || !self.rotated {
+            return Spin::None;
+        }
+
+        let filled =
+            |(x, y): (usize, usize)| !(4..14).contains(&x) || y < 4 || self.matrix[y][x].is_some();
+        // Clockwise from the top-left, the T points between the first two
+        let mut corners = [(x, y), (x + 2, y), (x + 2, y - 2), (x, y - 2)];
+        corners.rotate_left(rot as usize % 4);
+        let front = corners[..2].iter().filter(|c| filled(**c)).count();
+        let back = corners[2..].iter().filter(|c| filled(**c)).count();
+
+        match (front, back) {
+            (2, b) if b > 0 => Spin::Full,
+            (1, 2) => Spin::Mini,
+            _ => Spin::None,
+        }
+    }
+
+    fn push_bag(&mut self) {
+        let mut bag = vec![
+            Tetrimino::O,
+            Tetrimino::I,
+            Tetrimino::T,
+            Tetrimino::L,
+            Tetrimino::J,
+            Tetrimino::S,
+            Tetrimino::Z,
+        ];
+        bag.shuffle(&mut self.rng);
+        self.queue.extend(bag);
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Spin {
+    None,
+    Mini,
+    Full,
+}
+
+pub struct Clear {
+    pub lines: usize,
+    pub spin: Spin,
+    pub back_to_back: bool,
+    pub combo: i32,
+    pub perfect: bool,
+}
+
+impl Clear {
+    // Action text to announce, empty for unremarkable clears
+    pub fn text(&self) -> Vec<String> {
+        let name = match self.lines {
+            0 => "",
+            1 => "SINGLE",
+            2 => "DOUBLE",
+            3 => "TRIPLE",
+            _ => "TETRIS",
+        };
+        let mut text = Vec::new();
+        match self.spin {
+            Spin::None if self.lines == 4 => text.push(String::from(name)),
+            Spin::None => {}
+            Spin::Mini => text.push(format!("T-SPIN MINI {}", name)),
+            Spin::Full => text.push(format!("T-SPIN {}", name)),
+        }
+        if self.back_to_back {
+            text.push(String::from("BACK-TO-BACK"));
+        }
+        if self.combo > 0 {
+            text.push(format!("{} COMBO", self.combo));
+        }
+        if self.perfect {
+            text.push(String::from("PERFECT CLEAR"));
+        }
+        for line in text.iter_mut() {
+            *line = String::from(line.trim());
+        }
+        text
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum GameState {
+    Spawn,
+    Drop,
+    ClearLines,
+    Settings,
+    Die,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Tetrimino {
+    O,
+    I,
+    T,
+    L,
+    J,
+    S,
+    Z,
+    Ghost,
+    Garbage, // only ever locked in the matrix
+}
+
+impl Tetrimino {
+    // Spawns in rows 21 and 22 and drops one row right away if possible, returns whether it is blocked
+    pub fn spawn(self, data: &mut GameData) -> bool {
+        let (x, mut y) = (7, 25);
+        let res = self.collides(&data.matrix, (x, y), 0);
+        if !res && !self.collides(&data.matrix, (x, y - 1), 0) {
+            y -= 1;
+        }
+        data.active_mino = (self, x, y, 0);
+        data.rotated = false;
+        data.moves = 0;
+        res
+    }
+
+    pub fn rotate(
+        self,
+        data: &mut GameData,
+        pos: (usize, usize),
+        old_rot: u8,
+        new_rot: u8,
+    ) -> bool {
+        let mut tests: Vec<(isize, isize)> = vec![(0, 0)];
+
+        if data.active_mino.0 != Tetrimino::I {
+            for i in 0..4 {
+                tests.push(match (old_rot, new_rot) {
+                    (0, 1) | (2, 1) => match i {
+                        0 => (-1, 0),
+                        1 => (-1, 1),
+                        2 => (0, -2),
+                        3 => (-1, -2),
+                        _ => unreachable!(),
+                    },
+                    (1, 0) | (1, 2) => match i {
+                        0 => (1, 0),
+                        1 => (1, -1),
+                        2 => (0, 2),
+                        3 => (1, 2),
+                        _ => unreachable!(),
+                    },
+                    (2, 3) | (0, 3) => match i {
+                        0 => (1, 0),
+                        1 => (1, 1),
+                        2 => (0, -2),
+                        3 => (1, -2),
+                        _ => unreachable!(),
+                    },
+                    (3, 2) | (3, 0) => match i {
+                        0 => (-1, 0),
+                        1 => (-1, -1),
+                        2 => (0, 2),
+                        3 => (-1, 2),
+                        _ => unreachable!(),
+                    },
+
+                    _ => unreachable!(),
+                });
+            }
+        } else {
+            for i in 0..4 {
+                tests.push(match (old_rot, new_rot) {
+                    (0, 1) | (3, 2) => match i {
+                        0 => (-2, 0),
+                        1 => (1, 0),
+                        2 => (-2, 1),
+                        3 => (1, 2),
+                        _ => unreachable!(),
+                    },
+                    (1, 0) | (2, 3) => match i {
+                        0 => (2, 0),
+                        1 => (-1, 0),
+                        2 => (2, 1),
+                        3 => (-1, -2),
+                        _ => unreachable!(),
+                    },
+                    (1, 2) | (0, 3) => match i {
+                        0 => (-1, 0),
+                        1 => (2, 0),
+                        2 => (-1, 2),
+                        3 => (2, -1),
+                        _ => unreachable!(),
+                    },
+                    (2, 1) | (3, 0) => match i {
+                        0 => (1, 0),
+                        1 => (-2, 0),
+                        2 => (1, -2),
+                        3 => (-2, 1),
+                        _ => unreachable!(),
+                    },
+
+                    _ => unreachable!(),
+                });
+            }
+        }
+
+        for (off_x, off_y) in tests.iter() {
+            let (x, y) = (
+                (pos.0 as isize + off_x) as usize,
+                (pos.1 as isize + off_y) as usize,
+            );
+            if !self.collides(&data.matrix, (x, y), new_rot) {
+                data.active_mino.1 = x;
+                data.active_mino.2 = y;
+                data.active_mino.3 = new_rot;
+                data.rotated = true;
+                return true;
+            }
+        }
+        false
+    }
+
+    // Matrix cells covered by the piece at `pos`
+    pub fn cells(self, pos: (usize, usize), rot: u8) -> Vec<(usize, usize)> {
+        let block = ((get_rotation(self) >> (48 - (rot % 4) * 16)) & 0xFFFF) as u16;
+        let mut cells = Vec::new();
+        for y in 0..4 {
+            for x in 0..4 {
+                if block & (0x8000 >> (y * 4 + x)) != 0 {
+                    cells.push((pos.0 + x, pos.1.wrapping_sub(y)));
+                }
+            }
+        }
+        cells
+    }
+
+    // Whether the piece at `pos` overlaps locked blocks or leaves the matrix.
+    // pos is top-left corner of: https://tetris.fandom.com/wiki/SRS?file=SRS-pieces.png
+    pub fn collides(self, matrix: &Matrix, pos: (usize, usize), rot: u8) -> bool {
+        self.cells(pos, rot)
+            .iter()
+            .any(|(x, y)| !(4..14).contains(x) || !(4..44).contains(y) || matrix[*y][*x].is_some())
+    }
+}
+
+/*
+All 4 rotations of a piece encoded as a u64.
+// TODO explain this lol
+ */
+pub const ROTATIONS: [u64; 7] = [
+    0x6600660066006600, // O
+    0x0F00222200F04444, // I
+    0x4E0046400E404C40, // T
+    0x2E0044600E80C440, // L
+    0x8E0064400E2044C0, // J
+    0x6C00462006C08C40, // S
+    0xC60026400C604C80, // Z
+];
+
+pub fn get_rotation(tetrimino: Tetrimino) -> &'static u64 {
+    &ROTATIONS[match tetrimino {
+        Tetrimino::O => 0,
+        Tetrimino::I => 1,
+        Tetrimino::T => 2,
+        Tetrimino::L => 3,
+        Tetrimino::J => 4,
+        Tetrimino::S => 5,
+        Tetrimino::Z => 6,
+        _ => unreachable!(),
+    }]
+}
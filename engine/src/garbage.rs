@@ -0,0 +1,405 @@
+use crate::game::{Clear, Spin};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+// Extra lines for the nth clear in a row, the last entry repeats
+const COMBO: [u32; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+// Same for clears that send nothing on their own in the TETR.IO table
+const TETRIO_COMBO: [u32; 17] = [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3];
+const PERFECT_CLEAR: u32 = 10;
+
+// Survival sends a line every SURVIVAL_START ms at first, then faster down to SURVIVAL_MIN
+const SURVIVAL_START: u64 = 5000;
+const SURVIVAL_STEP: u64 = 100;
+const SURVIVAL_MIN: u64 = 1000;
+
+// How clears turn into attacks
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AttackTable {
+    Guideline,
+    Tetrio,  // combos multiply the attack instead of adding to it
+    Classic, // lines only, no bonuses
+}
+
+pub const ATTACK_TABLES: [AttackTable; 3] = [
+    AttackTable::Guideline,
+    AttackTable::Tetrio,
+    AttackTable::Classic,
+];
+
+impl AttackTable {
+    pub fn name(self) -> &'static str {
+        match self {
+            AttackTable::Guideline => "guideline",
+            AttackTable::Tetrio => "tetrio",
+            AttackTable::Classic => "classic",
+        }
+    }
+
+    pub fn attack(self, clear: &Clear) -> u32 {
+        match self {
+            AttackTable::Guideline => attack(clear),
+            AttackTable::Tetrio => tetrio_attack(clear),
+            AttackTable::Classic => match clear.lines {
+                4 => 4,
+                lines => (lines as u32).saturating_sub(1),
+            },
+        }
+    }
+}
+
+// What an attack does to the sender's own incoming garbage
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Counter {
+    Cancel, // cancels it, only the rest is sent
+    Offset, // cancels it and is still sent in full
+}
+
+impl Counter {
+    pub fn name(self) -> &'static str {
+        match self {
+            Counter::Cancel => "cancel",
+            Counter::Offset => "offset",
+        }
+    }
+}
+
+// Where the holes in rising garbage are
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Holes {
+    Clean, // one column for every line of an attack
+    Messy, // a new column for every line
+}
+
+impl Holes {
+    pub fn name(self) -> &'static str {
+        match self {
+            Holes::Clean => "clean",
+            Holes::Messy => "messy",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Rules {
+    pub table: AttackTable,
+    pub delay: u64, // ms before received garbage can rise
+    pub counter: Counter,
+    pub holes: Holes,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            table: AttackTable::Guideline,
+            delay: 0,
+            counter: Counter::Cancel,
+            holes: Holes::Clean,
+        }
+    }
+}
+
+// What a clear of at least one line sends for its lines and spin alone, the same in both tables
+fn base(clear: &Clear) -> u32 {
+    match (clear.spin, clear.lines) {
+        (Spin::Full, lines) => 2 * lines as u32,
+        (Spin::Mini, lines) => lines as u32 - 1,
+        (Spin::None, 4) => 4,
+        (Spin::None, lines) => lines as u32 - 1,
+    }
+}
+
+// Lines a clear sends, following the guideline
+pub fn attack(clear: &Clear) -> u32 {
+    if clear.lines == 0 {
+        return 0;
+    }
+    let base = base(clear);
+    let combo = COMBO[(clear.combo.max(0) as usize).min(COMBO.len() - 1)];
+    let perfect = if clear.perfect { PERFECT_CLEAR } else { 0 };
+    base + clear.back_to_back as u32 + combo + perfect
+}
+
+// TETR.IO style: the guideline base, every combo step adds a quarter of it on top
+fn tetrio_attack(clear: &Clear) -> u32 {
+    if clear.lines == 0 {
+        return 0;
+    }
+    let base = base(clear) + clear.back_to_back as u32;
+    let combo = clear.combo.max(0) as u32;
+    let attack = if base == 0 {
+        TETRIO_COMBO[(combo as usize).min(TETRIO_COMBO.len() - 1)]
+    } else {
+        base * (4 + combo) / 4
+    };
+    let perfect = if clear.perfect { PERFECT_CLEAR } else { 0 };
+    attack + perfect
+}
+
+// Incoming garbage, each attack waits for its entry delay before it can rise
+pub struct Garbage {
+    queue: VecDeque<(u32, u64)>, // lines and when they can rise
+    rules: Rules,
+    rng: SmallRng,
+}
+
+impl Garbage {
+    pub fn new(seed: u64, rules: Rules) -> Self {
+        Self {
+            queue: VecDeque::new(),
+            rules,
+            rng: SmallRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
+    // Lines waiting to rise, shown on the meter
+    pub fn pending(&self) -> u32 {
+        self.queue.iter().map(|(lines, _)| lines).sum()
+    }
+
+    pub fn add(&mut self, lines: u32, now: u64) {
+        if lines > 0 {
+            self.queue.push_back((lines, now + self.rules.delay));
+        }
+    }
+
+    // Lines a clear sends, after it has countered incoming garbage
+    pub fn attack(&mut self, clear: &Clear) -> u32 {
+        let attack = self.rules.table.attack(clear);
+        let left = self.cancel(attack);
+        match self.rules.counter {
+            Counter::Cancel => left,
+            Counter::Offset => attack,
+        }
+    }
+
+    // Cancels incoming garbage with an attack, oldest first, returns what is left to send
+    pub fn cancel(&mut self, mut attack: u32) -> u32 {
+        while attack > 0 {
+            match self.queue.front_mut() {
+                Some((lines, _)) if *lines > attack => {
+                    *lines -= attack;
+                    attack = 0;
+                }
+                Some((lines, _)) => {
+                    attack -= *lines;
+                    self.queue.pop_front();
+                }
+                None => break,
+            }
+        }
+        attack
+    }
+
+    // Everything past its entry delay as (lines, hole column)
+    pub fn take(&mut self, now: u64) -> Vec<(usize, usize)> {
+        let mut batches = Vec::new();
+        while let Some((lines, _)) = self.queue.front().filter(|(_, at)| *at <= now) {
+            let lines = *lines as usize;
+            self.queue.pop_front();
+            match self.rules.holes {
+                Holes::Clean => batches.push((lines, self.rng.gen_range(0..10))),
+                Holes::Messy => {
+                    batches.extend((0..lines).map(|_| (1, self.rng.gen_range(0..10))));
+                }
+            }
+        }
+        batches
+    }
+}
+
+// Garbage that arrives on its own in survival, a line at a time and a little faster every time
+pub struct Survival {
+    interval: u64,
+    next_at: u64,
+}
+
+impl Survival {
+    pub fn new(now: u64) -> Self {
+        Self {
+            interval: SURVIVAL_START,
+            next_at: now + SURVIVAL_START,
+        }
+    }
+
+    // Lines due by `now`
+    pub fn due(&mut self, now: u64) -> u32 {
+        let mut lines = 0;
+        while now >= self.next_at {
+            lines += 1;
+            self.interval = (self.interval - SURVIVAL_STEP).max(SURVIVAL_MIN);
+            self.next_at += self.interval;
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear(lines: usize, spin: Spin) -> Clear {
+        Clear {
+            lines,
+            spin,
+            back_to_back: false,
+            combo: 0,
+            perfect: false,
+        }
+    }
+
+    fn rules(counter: Counter, holes: Holes, delay: u64) -> Rules {
+        Rules {
+            table: AttackTable::Guideline,
+            delay,
+            counter,
+            holes,
+        }
+    }
+
+    #[test]
+    fn guideline_table() {
+        let table = AttackTable::Guideline;
+        let sent = |lines, spin| table.attack(&clear(lines, spin));
+        assert_eq!(
+            [0, 1, 2, 3, 4].map(|lines| sent(lines, Spin::None)),
+            [0, 0, 1, 2, 4]
+        );
+        assert_eq!([1, 2, 3].map(|lines| sent(lines, Spin::Full)), [2, 4, 6]);
+        assert_eq!([1, 2].map(|lines| sent(lines, Spin::Mini)), [0, 1]);
+
+        let bonus = |back_to_back, combo, perfect| {
+            table.attack(&Clear {
+                back_to_back,
+                combo,
+                perfect,
+                ..clear(4, Spin::None)
+            })
+        };
+        assert_eq!(bonus(true, 0, false), 5);
+        assert_eq!(bonus(false, 2, false), 5);
+        assert_eq!(bonus(false, 50, false), 9);
+        assert_eq!(bonus(false, 0, true), 14);
+    }
+
+    #[test]
+    fn tetrio_table() {
+        let table = AttackTable::Tetrio;
+        let sent = |lines, spin, back_to_back, combo| {
+            table.attack(&Clear {
+                back_to_back,
+                combo,
+                ..clear(lines, spin)
+            })
+        };
+        assert_eq!(sent(4, Spin::None, false, 0), 4);
+        assert_eq!(sent(4, Spin::None, true, 0), 5);
+        assert_eq!(sent(4, Spin::None, false, 4), 8);
+        assert_eq!(sent(2, Spin::Full, false, 2), 6);
+        // Singles only send their combo
+        assert_eq!(sent(1, Spin::None, false, 0), 0);
+        assert_eq!(sent(1, Spin::None, false, 2), 1);
+        assert_eq!(sent(1, Spin::None, false, 50), 3);
+        assert_eq!(sent(0, Spin::None, false, -1), 0);
+    }
+
+    #[test]
+    fn classic_table() {
+        let table = AttackTable::Classic;
+        let sent = |lines, spin| {
+            table.attack(&Clear {
+                back_to_back: true,
+                combo: 5,
+                perfect: true,
+                ..clear(lines, spin)
+            })
+        };
+        assert_eq!(
+            [0, 1, 2, 3, 4].map(|lines| sent(lines, Spin::None)),
+            [0, 0, 1, 2, 4]
+        );
+        assert_eq!(sent(2, Spin::Full), 1);
+    }
+
+    #[test]
+    fn cancel_sends_what_is_left() {
+        let mut garbage = Garbage::new(0, rules(Counter::Cancel, Holes::Clean, 0));
+        garbage.add(3, 0);
+        assert_eq!(garbage.attack(&clear(4, Spin::None)), 1);
+        assert_eq!(garbage.pending(), 0);
+
+        garbage.add(2, 0);
+        garbage.add(3, 0);
+        assert_eq!(garbage.attack(&clear(4, Spin::None)), 0);
+        assert_eq!(garbage.pending(), 1);
+    }
+
+    #[test]
+    fn offset_sends_everything() {
+        let mut garbage = Garbage::new(0, rules(Counter::Offset, Holes::Clean, 0));
+        garbage.add(3, 0);
+        assert_eq!(garbage.attack(&clear(4, Spin::None)), 4);
+        assert_eq!(garbage.pending(), 0);
+
+        garbage.add(6, 0);
+        assert_eq!(garbage.attack(&clear(4, Spin::None)), 4);
+        assert_eq!(garbage.pending(), 2);
+    }
+
+    #[test]
+    fn clean_and_messy_holes() {
+        let mut clean = Garbage::new(7, rules(Counter::Cancel, Holes::Clean, 0));
+        clean.add(3, 0);
+        clean.add(2, 0);
+        let batches = clean.take(0);
+        assert_eq!(
+            batches.iter().map(|(lines, _)| *lines).collect::<Vec<_>>(),
+            [3, 2]
+        );
+        assert!(batches.iter().all(|(_, hole)| *hole < 10));
+
+        let mut messy = Garbage::new(7, rules(Counter::Cancel, Holes::Messy, 0));
+        messy.add(3, 0);
+        messy.add(2, 0);
+        let batches = messy.take(0);
+        assert_eq!(batches.len(), 5);
+        assert!(batches
+            .iter()
+            .all(|(lines, hole)| *lines == 1 && *hole < 10));
+    }
+
+    #[test]
+    fn garbage_waits_for_its_delay() {
+        let mut garbage = Garbage::new(0, rules(Counter::Cancel, Holes::Clean, 500));
+        garbage.add(2, 100);
+        garbage.add(1, 300);
+        assert!(garbage.take(599).is_empty());
+        assert_eq!(garbage.pending(), 3);
+        assert_eq!(garbage.take(600).len(), 1);
+        assert_eq!(garbage.pending(), 1);
+        assert_eq!(garbage.take(800).len(), 1);
+        assert_eq!(garbage.pending(), 0);
+    }
+
+    #[test]
+    fn survival_speeds_up() {
+        let mut survival = Survival::new(0);
+        assert_eq!(survival.due(SURVIVAL_START - 1), 0);
+        assert_eq!(survival.due(SURVIVAL_START), 1);
+        let next = SURVIVAL_START + SURVIVAL_START - SURVIVAL_STEP;
+        assert_eq!(survival.due(next - 1), 0);
+        assert_eq!(survival.due(next), 1);
+
+        // A long pause catches up on every line and bottoms out the interval
+        assert!(survival.due(1_000_000) > 100);
+        assert_eq!(survival.interval, SURVIVAL_MIN);
+        assert_eq!(survival.due(survival.next_at - 1), 0);
+        assert_eq!(survival.due(survival.next_at), 1);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::settings::Settings;
+use alloc::vec::Vec;
+use embedded_graphics::prelude::*;
+
+// Sizes in blocks
+const BOARD: (u32, u32) = (10, 20);
+const SIDE_WIDTH: u32 = 5;
+const HOLD_HEIGHT: u32 = 3;
+const GAP: u32 = 1;
+// Space between the boards of a versus layout
+const VERSUS_GAP: u32 = 2;
+// Width of one board with its side panels
+const CLUSTER_WIDTH: u32 = BOARD.0 + 2 * (SIDE_WIDTH + GAP);
+// Rows above (messages) and below (timer) the boards
+const MARGIN: (u32, u32) = (4, 2);
+
+pub const BLOCK_SIZES: [u32; 4] = [8, 16, 24, 32];
+
+// Which hand the panels favour: right-handed puts the hold box left of the
+// board and the next queue right of it, left-handed mirrors that
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Hand {
+    Right,
+    Left,
+}
+
+impl Hand {
+    pub fn name(self) -> &'static str {
+        match self {
+            Hand::Right => "right",
+            Hand::Left => "left",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Rect {
+    pub top_left: Point,
+    pub size: Size,
+}
+
+impl Rect {
+    fn blocks(x: i32, y: i32, width: u32, height: u32, block: u32) -> Self {
+        Self {
+            top_left: Point::new(x, y),
+            size: Size::new(width * block, height * block),
+        }
+    }
+
+    pub fn bottom_right(&self) -> Point {
+        self.top_left + Point::new(self.size.width as i32 - 1, self.size.height as i32 - 1)
+    }
+}
+
+// Panels belonging to a single player
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct BoardLayout {
+    pub board: Rect,
+    pub buffer: Rect, // partially visible rows above the board
+    pub meter: Rect,  // incoming garbage, in the gap left of the board
+    pub hold: Rect,
+    pub queue: Rect,
+    pub stats: Rect,
+    pub timer: Rect,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Layout {
+    pub block: u32,
+    pub previews: usize,
+    pub boards: Vec<BoardLayout>,
+    pub messages: Rect,
+}
+
+impl Layout {
+    // A block size of 0 picks the largest one that fits the screen
+    pub fn new(screen: Size, settings: &Settings, players: u32) -> Self {
+        let block = if settings.block == 0 {
+            BLOCK_SIZES
+                .iter()
+                .rev()
+                .cloned()
+                .find(|b| fits(screen, *b, players))
+                .unwrap_or(BLOCK_SIZES[0])
+        } else {
+            settings.block
+        };
+        // Three rows per piece, squeezed to fit next to the board
+        let queue_height = (settings.previews as u32 * 3).min(BOARD.1);
+
+        let (width, height) = size(players);
+        // Centered, anything that doesn't fit is cut off on the right and bottom
+        let origin = Point::new(
+            (screen.width.saturating_sub(width * block) / 2) as i32,
+            (screen.height.saturating_sub(height * block) / 2) as i32,
+        );
+        let b = block as i32;
+
+        let boards = (0..players)
+            .map(|idx| {
+                let left = origin.x + ((CLUSTER_WIDTH + VERSUS_GAP) * idx) as i32 * b;
+                let top = origin.y + MARGIN.0 as i32 * b;
+                let board_x = left + (SIDE_WIDTH + GAP) as i32 * b;
+                let right = board_x + (BOARD.0 + GAP) as i32 * b;
+                let (hold_x, queue_x) = match settings.hand {
+                    Hand::Right => (left, right),
+                    Hand::Left => (right, left),
+                };
+
+                let stats_y = top + (HOLD_HEIGHT + GAP) as i32 * b;
+                BoardLayout {
+                    board: Rect::blocks(board_x, top, BOARD.0, BOARD.1, block),
+                    buffer: Rect {
+                        top_left: Point::new(board_x, top - b * 3 / 2),
+                        size: Size::new(BOARD.0 * block, block * 3 / 2),
+                    },
+                    meter: Rect {
+                        top_left: Point::new(board_x - b / 2, top),
+                        size: Size::new(block / 2, BOARD.1 * block),
+                    },
+                    hold: Rect::blocks(hold_x, top, SIDE_WIDTH, HOLD_HEIGHT, block),
+                    queue: Rect::blocks(queue_x, top, SIDE_WIDTH, queue_height, block),
+                    stats: Rect::blocks(
+                        hold_x,
+                        stats_y,
+                        SIDE_WIDTH,
+                        BOARD.1 - HOLD_HEIGHT - GAP,
+                        block,
+                    ),
+                    timer: Rect::blocks(
+                        board_x,
+                        top + (BOARD.1 + GAP) as i32 * b,
+                        BOARD.0,
+                        1,
+                        block,
+                    ),
+                }
+            })
+            .collect();
+
+        Self {
+            block,
+            previews: settings.previews,
+            boards,
+            // Messages span the whole screen, they may not fit above a single board
+            messages: Rect::blocks(b, origin.y + b, screen.width / block - 2, 2, block),
+        }
+    }
+}
+
+// Size of the whole layout in blocks
+fn size(players: u32) -> (u32, u32) {
+    (
+        CLUSTER_WIDTH * players + VERSUS_GAP * (players - 1),
+        MARGIN.0 + BOARD.1 + MARGIN.1,
+    )
+}
+
+fn fits(screen: Size, block: u32, players: u32) -> bool {
+    let (width, height) = size(players);
+    width * block <= screen.width && height * block <= screen.height
+}
@@ -0,0 +1,25 @@
+#![no_std]
+extern crate alloc;
+
+pub mod ansi;
+pub mod bot;
+pub mod canvas;
+pub mod effects;
+pub mod finesse;
+pub mod frontend;
+pub mod fumen;
+pub mod game;
+pub mod garbage;
+pub mod layout;
+pub mod menu;
+pub mod net;
+pub mod player;
+pub mod puzzle;
+pub mod replay;
+pub mod settings;
+pub mod sprites;
+pub mod stats;
+pub mod tbp;
+pub mod text;
+pub mod ui;
+pub mod versus;
@@ -0,0 +1,140 @@
+use crate::settings::{KeyCode, Settings, SKIN_ITEM, VIDEO_MODE_ITEM};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub enum MenuEvent {
+    None,
+    Redraw,
+    Save,
+    Close,
+    PreviewMode(usize),
+    KeepMode,
+    RevertMode,
+    Benchmark,
+    Export,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub enum Page {
+    Settings,
+    VideoModes,
+    ConfirmMode,
+}
+
+pub struct Menu {
+    pub page: Page,
+    pub selected: usize,
+    pub mode: usize,
+    pub rebinding: bool,
+    skins: Vec<String>,
+    backup: Settings,
+}
+
+impl Menu {
+    // `skins` lists every selectable skin, including the built-in one
+    pub fn open(settings: &Settings, current_mode: usize, skins: Vec<String>) -> Self {
+        Self {
+            page: Page::Settings,
+            selected: 0,
+            mode: current_mode,
+            rebinding: false,
+            skins,
+            backup: settings.clone(),
+        }
+    }
+
+    // `modes` is the number of video modes offered by the mode picker
+    pub fn handle(&mut self, key: KeyCode, settings: &mut Settings, modes: usize) -> MenuEvent {
+        match self.page {
+            Page::Settings => self.handle_settings(key, settings),
+            Page::VideoModes => match key {
+                KeyCode::Up if modes > 0 => {
+                    self.mode = (self.mode + modes - 1) % modes;
+                    MenuEvent::Redraw
+                }
+                KeyCode::Down if modes > 0 => {
+                    self.mode = (self.mode + 1) % modes;
+                    MenuEvent::Redraw
+                }
+                KeyCode::Char('\r') if modes > 0 => {
+                    self.page = Page::ConfirmMode;
+                    MenuEvent::PreviewMode(self.mode)
+                }
+                KeyCode::Char('b') => MenuEvent::Benchmark,
+                KeyCode::Escape => {
+                    self.page = Page::Settings;
+                    MenuEvent::Redraw
+                }
+                _ => MenuEvent::None,
+            },
+            Page::ConfirmMode => match key {
+                KeyCode::Char('\r') => {
+                    self.page = Page::Settings;
+                    MenuEvent::KeepMode
+                }
+                _ => self.revert_mode(),
+            },
+        }
+    }
+
+    pub fn revert_mode(&mut self) -> MenuEvent {
+        self.page = Page::VideoModes;
+        MenuEvent::RevertMode
+    }
+
+    // A confirmed video mode is persisted right away and survives cancelling the menu
+    pub fn keep_mode(&mut self, settings: &mut Settings, resolution: (u32, u32)) {
+        settings.width = resolution.0;
+        settings.height = resolution.1;
+        self.backup.width = resolution.0;
+        self.backup.height = resolution.1;
+    }
+
+    fn handle_settings(&mut self, key: KeyCode, settings: &mut Settings) -> MenuEvent {
+        if self.rebinding {
+            self.rebinding = false;
+            settings.bind(Settings::binding(self.selected).unwrap(), key);
+            return MenuEvent::Redraw;
+        }
+
+        let count = settings.items().len();
+        match key {
+            KeyCode::Up => self.selected = (self.selected + count - 1) % count,
+            KeyCode::Down => self.selected = (self.selected + 1) % count,
+            KeyCode::Left if self.selected == SKIN_ITEM => self.cycle_skin(settings, -1),
+            KeyCode::Right if self.selected == SKIN_ITEM => self.cycle_skin(settings, 1),
+            KeyCode::Left => settings.adjust(self.selected, -1),
+            KeyCode::Right => settings.adjust(self.selected, 1),
+            KeyCode::Char('\r') => {
+                if self.selected == VIDEO_MODE_ITEM {
+                    self.page = Page::VideoModes;
+                } else if Settings::binding(self.selected).is_some() {
+                    self.rebinding = true;
+                } else {
+                    return MenuEvent::None;
+                }
+            }
+            KeyCode::Char('s') => return MenuEvent::Save,
+            KeyCode::Char('x') => return MenuEvent::Export,
+            KeyCode::Escape => {
+                *settings = self.backup.clone();
+                return MenuEvent::Close;
+            }
+            _ => return MenuEvent::None,
+        }
+        MenuEvent::Redraw
+    }
+
+    fn cycle_skin(&mut self, settings: &mut Settings, delta: isize) {
+        if self.skins.is_empty() {
+            return;
+        }
+        let idx = self
+            .skins
+            .iter()
+            .position(|skin| *skin == settings.skin)
+            .unwrap_or(0) as isize;
+        let idx = (idx + delta).rem_euclid(self.skins.len() as isize) as usize;
+        settings.skin = self.skins[idx].clone();
+    }
+}
@@ -0,0 +1,449 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+// Each machine runs its own game, only attacks and top outs cross the wire
+const MAGIC: &[u8; 4] = b"RSTR";
+const VERSION: u8 = 1;
+pub const MESSAGE_SIZE: usize = 22;
+
+// In ms of real time
+const HELLO_INTERVAL: u64 = 500;
+const RESEND_INTERVAL: u64 = 200;
+const KEEPALIVE_INTERVAL: u64 = 500;
+const TIMEOUT: u64 = 5000;
+
+// Broadcasts one frame to the other machines and reads the next frame that arrived, if any
+pub trait Link {
+    fn send(&mut self, data: &[u8]);
+    fn recv(&mut self, buffer: &mut [u8]) -> Option<usize>;
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Message {
+    Hello { seed: u64, peer: u32 }, // 0 while no peer is chosen
+    Garbage { seq: u32, lines: u32 },
+    Lost { seq: u32 }, // the sender topped out
+    Ack { seq: u32 },  // everything up to `seq` arrived
+    Alive,
+}
+
+impl Message {
+    fn kind(&self) -> u8 {
+        match self {
+            Message::Hello { .. } => 0,
+            Message::Garbage { .. } => 1,
+            Message::Lost { .. } => 2,
+            Message::Ack { .. } => 3,
+            Message::Alive => 4,
+        }
+    }
+
+    // Magic, version, kind, sender id and up to 12 bytes of fields, little endian
+    fn encode(&self, id: u32) -> [u8; MESSAGE_SIZE] {
+        let mut out = [0; MESSAGE_SIZE];
+        out[..4].copy_from_slice(MAGIC);
+        out[4] = VERSION;
+        out[5] = self.kind();
+        out[6..10].copy_from_slice(&id.to_le_bytes());
+        let fields = &mut out[10..];
+        match *self {
+            Message::Hello { seed, peer } => {
+                fields[..8].copy_from_slice(&seed.to_le_bytes());
+                fields[8..].copy_from_slice(&peer.to_le_bytes());
+            }
+            Message::Garbage { seq, lines } => {
+                fields[..4].copy_from_slice(&seq.to_le_bytes());
+                fields[4..8].copy_from_slice(&lines.to_le_bytes());
+            }
+            Message::Lost { seq } | Message::Ack { seq } => {
+                fields[..4].copy_from_slice(&seq.to_le_bytes());
+            }
+            Message::Alive => {}
+        }
+        out
+    }
+
+    // Other traffic on the network is ignored
+    fn decode(data: &[u8]) -> Option<(u32, Message)> {
+        if data.len() < MESSAGE_SIZE || &data[..4] != MAGIC || data[4] != VERSION {
+            return None;
+        }
+        let u32_at = |pos: usize| {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(&data[pos..pos + 4]);
+            u32::from_le_bytes(bytes)
+        };
+        let message = match data[5] {
+            0 => {
+                let mut seed = [0; 8];
+                seed.copy_from_slice(&data[10..18]);
+                Message::Hello {
+                    seed: u64::from_le_bytes(seed),
+                    peer: u32_at(18),
+                }
+            }
+            1 => Message::Garbage {
+                seq: u32_at(10),
+                lines: u32_at(14),
+            },
+            2 => Message::Lost { seq: u32_at(10) },
+            3 => Message::Ack { seq: u32_at(10) },
+            4 => Message::Alive,
+            _ => return None,
+        };
+        Some((u32_at(6), message))
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Event {
+    Start { seed: u64 }, // both sides play the pieces from this seed
+    Garbage(u32),
+    Won,
+    Disconnected,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum State {
+    Searching,
+    Playing,
+    Over,
+}
+
+// One match against the first machine that answers, ids have to be unique on the network
+pub struct Session {
+    id: u32,
+    seed: u64,
+    state: State,
+    peer: Option<(u32, u64)>, // id and seed
+    next_seq: u32,
+    unacked: VecDeque<Message>,
+    received: u32, // last garbage or top out taken from the peer
+    lost: bool,
+    last_sent: Option<u64>,
+    last_heard: u64,
+    answered: Option<u64>, // last hello answered during the match
+}
+
+impl Session {
+    pub fn new(id: u32, seed: u64) -> Self {
+        Self {
+            id,
+            seed,
+            state: State::Searching,
+            peer: None,
+            next_seq: 1,
+            unacked: VecDeque::new(),
+            received: 0,
+            lost: false,
+            last_sent: None,
+            last_heard: 0,
+            answered: None,
+        }
+    }
+
+    pub fn searching(&self) -> bool {
+        self.state == State::Searching
+    }
+
+    pub fn send_garbage(&mut self, lines: u32) {
+        if lines > 0 && self.state == State::Playing {
+            self.queue(|seq| Message::Garbage { seq, lines });
+        }
+    }
+
+    // Stays open until the peer has heard about it
+    pub fn lose(&mut self) {
+        if self.state == State::Playing && !self.lost {
+            self.lost = true;
+            self.queue(|seq| Message::Lost { seq });
+        }
+    }
+
+    fn queue(&mut self, message: impl FnOnce(u32) -> Message) {
+        self.unacked.push_back(message(self.next_seq));
+        self.next_seq += 1;
+        // Sent with the next update
+        self.last_sent = None;
+    }
+
+    fn send<L: Link>(&mut self, link: &mut L, message: Message, now: u64) {
+        link.send(&message.encode(self.id));
+        self.last_sent = Some(now);
+    }
+
+    // Handles everything that arrived and sends what is due, `now` keeps running while the game is paused
+    pub fn update<L: Link>(&mut self, link: &mut L, now: u64) -> Vec<Event> {
+        let mut events = Vec::new();
+        let mut buffer = [0; 1536];
+        while let Some(len) = link.recv(&mut buffer) {
+            if let Some((id, message)) = Message::decode(&buffer[..len]) {
+                if id != self.id {
+                    self.handle(link, id, message, now, &mut events);
+                }
+            }
+        }
+
+        match self.state {
+            State::Searching => {
+                if self.due(now, HELLO_INTERVAL) {
+                    self.send(link, self.hello(), now);
+                }
+            }
+            State::Playing | State::Over => {
+                if !self.unacked.is_empty() && self.due(now, RESEND_INTERVAL) {
+                    // Cheap enough to resend everything, the receiver drops what it already has
+                    for message in self.unacked.clone() {
+                        self.send(link, message, now);
+                    }
+                } else if self.state == State::Playing && self.due(now, KEEPALIVE_INTERVAL) {
+                    self.send(link, Message::Alive, now);
+                }
+                if self.state == State::Playing && now.saturating_sub(self.last_heard) >= TIMEOUT {
+                    self.state = State::Over;
+                    events.push(Event::Disconnected);
+                }
+            }
+        }
+        events
+    }
+
+    // Whether nothing was sent for `interval` ms
+    fn due(&self, now: u64, interval: u64) -> bool {
+        match self.last_sent {
+            Some(at) => now.saturating_sub(at) >= interval,
+            None => true,
+        }
+    }
+
+    fn hello(&self) -> Message {
+        Message::Hello {
+            seed: self.seed,
+            peer: self.peer.map_or(0, |(id, _)| id),
+        }
+    }
+
+    fn handle<L: Link>(
+        &mut self,
+        link: &mut L,
+        id: u32,
+        message: Message,
+        now: u64,
+        events: &mut Vec<Event>,
+    ) {
+        if self.state == State::Searching {
+            // Both sides pick each other before the match starts
+            if let Message::Hello { seed, peer } = message {
+                let chosen = self.peer.map(|(peer, _)| peer);
+                if chosen == Some(id) && peer != 0 && peer != self.id {
+                    // It went for someone else
+                    self.peer = None;
+                } else if chosen.is_none() && (peer == 0 || peer == self.id) {
+                    self.peer = Some((id, seed));
+                }
+                if self.peer.map(|(peer, _)| peer) == Some(id) && peer == self.id {
+                    self.state = State::Playing;
+                    self.last_heard = now;
+                    self.send(link, self.hello(), now);
+                    // The lower id's pieces
+                    let seed = if id < self.id { seed } else { self.seed };
+                    events.push(Event::Start { seed });
+                }
+            }
+            return;
+        }
+        if self.peer.map(|(peer, _)| peer) != Some(id) {
+            return;
+        }
+        self.last_heard = now;
+
+        match message {
+            // The peer may have missed the hello that started the match. Answered once per
+            // interval, or two sides that both started would echo it back and forth forever
+            Message::Hello { peer, .. } if peer == self.id => {
+                if !matches!(self.answered, Some(at) if now.saturating_sub(at) < HELLO_INTERVAL) {
+                    self.answered = Some(now);
+                    self.send(link, self.hello(), now);
+                }
+            }
+            Message::Hello { .. } => {
+                if self.state == State::Playing {
+                    self.state = State::Over;
+                    events.push(Event::Disconnected);
+                }
+            }
+            Message::Garbage { seq, .. } | Message::Lost { seq } => {
+                // Only in order, anything after a gap is resent anyway
+                if seq == self.received + 1 {
+                    self.received = seq;
+                    match message {
+                        Message::Garbage { lines, .. } if self.state == State::Playing => {
+                            events.push(Event::Garbage(lines))
+                        }
+                        Message::Lost { .. } if self.state == State::Playing => {
+                            self.state = State::Over;
+                            // Both topping out at once counts as a loss for both
+                            if !self.lost {
+                                events.push(Event::Won);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                self.send(link, Message::Ack { seq: self.received }, now);
+            }
+            Message::Ack { seq } => {
+                while let Some(message) = self.unacked.front() {
+                    match message {
+                        Message::Garbage { seq: sent, .. } | Message::Lost { seq: sent }
+                            if *sent <= seq =>
+                        {
+                            self.unacked.pop_front();
+                        }
+                        _ => break,
+                    }
+                }
+                if self.lost && self.unacked.is_empty() {
+                    self.state = State::Over;
+                }
+            }
+            Message::Alive => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::rc::Rc;
+    use alloc::vec;
+    use core::cell::RefCell;
+
+    type Queue = Rc<RefCell<VecDeque<Vec<u8>>>>;
+
+    // One end of an in-memory cable that can lose or repeat the frames it sends
+    #[derive(Default)]
+    struct Wire {
+        inbox: Queue,
+        outbox: Queue,
+        sent: usize,
+        drop_every: usize, // 0 keeps every frame
+        duplicate: bool,
+    }
+
+    impl Link for Wire {
+        fn send(&mut self, data: &[u8]) {
+            self.sent += 1;
+            if self.drop_every > 0 && self.sent.is_multiple_of(self.drop_every) {
+                return;
+            }
+            let copies = if self.duplicate { 2 } else { 1 };
+            for _ in 0..copies {
+                self.outbox.borrow_mut().push_back(data.to_vec());
+            }
+        }
+
+        fn recv(&mut self, buffer: &mut [u8]) -> Option<usize> {
+            let frame = self.inbox.borrow_mut().pop_front()?;
+            buffer[..frame.len()].copy_from_slice(&frame);
+            Some(frame.len())
+        }
+    }
+
+    // Updates both sides every 50 ms from `from` until `to`, returns their events
+    fn run(
+        sessions: &mut [Session; 2],
+        wires: &mut [Wire; 2],
+        from: u64,
+        to: u64,
+    ) -> [Vec<Event>; 2] {
+        let mut events = [Vec::new(), Vec::new()];
+        for now in (from..to).step_by(50) {
+            let sides = sessions.iter_mut().zip(wires.iter_mut());
+            for ((session, wire), events) in sides.zip(events.iter_mut()) {
+                events.extend(session.update(wire, now));
+            }
+        }
+        events
+    }
+
+    // Two sessions on a cable, past the start of their match at 1000 ms
+    fn started() -> ([Session; 2], [Wire; 2]) {
+        let (there, back) = (Queue::default(), Queue::default());
+        let mut wires = [
+            Wire {
+                inbox: back.clone(),
+                outbox: there.clone(),
+                ..Wire::default()
+            },
+            Wire {
+                inbox: there,
+                outbox: back,
+                ..Wire::default()
+            },
+        ];
+        let mut sessions = [Session::new(1, 100), Session::new(2, 200)];
+        let start = vec![Event::Start { seed: 100 }];
+        assert_eq!(
+            run(&mut sessions, &mut wires, 0, 1000),
+            [start.clone(), start]
+        );
+        (sessions, wires)
+    }
+
+    #[test]
+    fn garbage_survives_dropped_frames() {
+        let (mut sessions, mut wires) = started();
+        for wire in wires.iter_mut() {
+            wire.drop_every = 2;
+        }
+        sessions[0].send_garbage(3);
+        sessions[0].send_garbage(2);
+        sessions[1].send_garbage(4);
+        let events = run(&mut sessions, &mut wires, 1000, 3000);
+        assert_eq!(events[0], [Event::Garbage(4)]);
+        assert_eq!(events[1], [Event::Garbage(3), Event::Garbage(2)]);
+        assert!(sessions.iter().all(|session| session.unacked.is_empty()));
+    }
+
+    #[test]
+    fn duplicated_frames_count_once() {
+        let (mut sessions, mut wires) = started();
+        for wire in wires.iter_mut() {
+            wire.duplicate = true;
+        }
+        sessions[0].send_garbage(3);
+        let events = run(&mut sessions, &mut wires, 1000, 1500);
+        sessions[0].send_garbage(2);
+        let later = run(&mut sessions, &mut wires, 1500, 3000);
+        assert_eq!(events[1], [Event::Garbage(3)]);
+        assert_eq!(later[1], [Event::Garbage(2)]);
+        assert!(events[0].is_empty() && later[0].is_empty());
+    }
+
+    #[test]
+    fn topping_out_wins_for_the_peer() {
+        let (mut sessions, mut wires) = started();
+        wires[0].drop_every = 3;
+        sessions[1].lose();
+        sessions[1].send_garbage(5);
+        let events = run(&mut sessions, &mut wires, 1000, 3000);
+        assert_eq!(events, [vec![Event::Won], vec![]]);
+        assert!(sessions.iter().all(|session| session.state == State::Over));
+    }
+
+    #[test]
+    fn silence_disconnects() {
+        let (mut sessions, mut wires) = started();
+        let heard = sessions[0].last_heard;
+        let mut events = Vec::new();
+        for now in (1000..heard + TIMEOUT).step_by(50) {
+            events.extend(sessions[0].update(&mut wires[0], now));
+        }
+        assert!(events.is_empty());
+        for now in (heard + TIMEOUT..heard + 2 * TIMEOUT).step_by(50) {
+            events.extend(sessions[0].update(&mut wires[0], now));
+        }
+        assert_eq!(events, [Event::Disconnected]);
+    }
+}
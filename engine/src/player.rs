@@ -0,0 +1,120 @@
+use crate::finesse;
+use crate::game::{Clear, GameData, GameState};
+use crate::garbage::Garbage;
+use crate::replay::{self, Input};
+use crate::settings::{Action, Settings};
+
+// A board with gravity and lock delay run off the game clock (ms) instead of firmware timers
+pub struct Player {
+    pub data: GameData,
+    pub garbage: Garbage, // incoming
+    drop_at: u64,         // time of the next gravity step
+    lock_at: Option<u64>,
+}
+
+impl Player {
+    // Spawns the first piece right away
+    pub fn new(seed: u64, garbage_seed: u64, now: u64, settings: &Settings) -> Self {
+        Self::with_data(GameData::new(seed), garbage_seed, now, settings)
+    }
+
+    // Plays on a prepared board and queue
+    pub fn with_data(data: GameData, garbage_seed: u64, now: u64, settings: &Settings) -> Self {
+        let mut player = Self {
+            data,
+            garbage: Garbage::new(garbage_seed, settings.garbage),
+            drop_at: now + settings.fall_speed,
+            lock_at: None,
+        };
+        // A game that comes with an active piece keeps it
+        if player.data.state != GameState::Drop {
+            replay::spawn(&mut player.data);
+        }
+        player
+    }
+
+    // The timer that ran out by `now`, if any
+    pub fn due(&self, now: u64) -> Option<Input> {
+        if self.data.state != GameState::Drop {
+            None
+        } else if matches!(self.lock_at, Some(at) if now >= at) {
+            Some(Input::Lock)
+        } else if now >= self.drop_at {
+            Some(Input::Gravity)
+        } else {
+            None
+        }
+    }
+
+    // Applies an input and restarts the timers it affects, returns what a lock cleared
+    pub fn input(&mut self, input: Input, now: u64, settings: &Settings) -> Option<Clear> {
+        let before = self.data.active_mino;
+        // Checked here rather than in `apply`, which the bot's search runs for every candidate
+        if input == Input::Lock && self.data.state == GameState::Drop {
+            self.data.stats.faults += finesse::fault(&self.data) as u32;
+        }
+        let clear = replay::apply(&mut self.data, input);
+
+        match input {
+            Input::Lock => {
+                if let Some(clear) = &clear {
+                    self.record_attack(clear);
+                }
+                self.lock_at = None;
+                self.drop_at = now + settings.fall_speed;
+                // Garbage only rises when a piece locks without clearing anything
+                if matches!(&clear, Some(clear) if clear.lines == 0) {
+                    self.raise_garbage(now);
+                }
+            }
+            _ => {
+                match input {
+                    Input::Gravity | Input::Action(Action::SoftDrop) => {
+                        self.drop_at = now + settings.fall_speed;
+                    }
+                    Input::Action(Action::Hold) => {
+                        self.lock_at = None;
+                        self.drop_at = now + settings.fall_speed;
+                    }
+                    _ => {}
+                }
+                // Lock delay starts on landing and restarts with every move on the ground
+                let (tetrimino, x, y, rot) = self.data.active_mino;
+                if !tetrimino.collides(&self.data.matrix, (x, y - 1), rot) {
+                    self.lock_at = None;
+                } else if self.lock_at.is_none() || self.data.active_mino != before {
+                    self.lock_at = Some(now + settings.lock_delay);
+                }
+                if input == Input::Action(Action::HardDrop) {
+                    self.lock_at = Some(now);
+                }
+            }
+        }
+        clear
+    }
+
+    // Lines a clear sends once it has countered the player's own incoming garbage
+    pub fn attack(&mut self, clear: &Clear) -> u32 {
+        self.garbage.attack(clear)
+    }
+
+    // Counts what a clear sends under this player's attack table in the stats
+    pub fn record_attack(&mut self, clear: &Clear) {
+        self.data.stats.attack += self.garbage.rules().table.attack(clear);
+    }
+
+    // The next piece has already spawned, so it spawns again on top of the risen stack
+    fn raise_garbage(&mut self, now: u64) {
+        let batches = self.garbage.take(now);
+        if batches.is_empty() || self.data.state != GameState::Drop {
+            return;
+        }
+        for (lines, hole) in batches {
+            self.data.add_garbage(lines, hole);
+        }
+        let tetrimino = self.data.active_mino.0;
+        if tetrimino.spawn(&mut self.data) {
+            self.data.state = GameState::Die;
+        }
+    }
+}
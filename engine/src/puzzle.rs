@@ -0,0 +1,358 @@
+use crate::fumen;
+use crate::game::{Clear, GameData, GameState, Matrix, Spin, Tetrimino};
+use crate::tbp;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// Rows a puzzle board can have when drawn as text
+const MAX_ROWS: usize = 20;
+
+// What a puzzle asks for
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Goal {
+    Lines(u32),
+    PerfectClear,
+    TSpinDouble,
+}
+
+impl Goal {
+    // `lines N`, `pc` or `tsd`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut words = value.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some("pc"), None, None) => Ok(Goal::PerfectClear),
+            (Some("tsd"), None, None) => Ok(Goal::TSpinDouble),
+            (Some("lines"), Some(lines), None) => match lines.parse() {
+                Ok(lines) if lines > 0 => Ok(Goal::Lines(lines)),
+                _ => Err(format!("invalid line count `{}`", lines)),
+            },
+            _ => Err(format!("unknown goal `{}`", value)),
+        }
+    }
+
+    pub fn describe(self) -> String {
+        match self {
+            Goal::Lines(1) => String::from("Clear a line"),
+            Goal::Lines(lines) => format!("Clear {} lines", lines),
+            Goal::PerfectClear => String::from("Perfect clear"),
+            Goal::TSpinDouble => String::from("T-spin double"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Outcome {
+    Solved,
+    Failed,
+}
+
+// A board, a fixed sequence of pieces and a goal to reach with them
+#[derive(Clone)]
+pub struct Puzzle {
+    pub name: String,
+    pub matrix: Matrix,
+    pub hold: Option<Tetrimino>,
+    pub pieces: Vec<Tetrimino>, // the first one starts
+    pub goal: Goal,
+}
+
+impl Puzzle {
+    // `key = value` lines like the settings: `name`, `goal`, `pieces`, `hold` and the board,
+    // either as `fumen = v115@...` or as `row = ...` lines from the top down.
+    // A file with nothing but a fumen in it is a perfect clear puzzle with the pieces of its quiz comment
+    pub fn parse(name: &str, src: &str) -> Result<Self, String> {
+        if !src.contains('=') {
+            return Self::from_fumen(name, src);
+        }
+
+        let mut name = String::from(name);
+        let mut goal = None;
+        let mut pieces = None;
+        let mut hold = None;
+        let mut fumen = None;
+        let mut rows = Vec::new();
+        for (idx, line) in src.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = match line.find('=') {
+                Some(pos) => (line[..pos].trim(), line[pos + 1..].trim()),
+                None => return Err(format!("line {}: expected `key = value`", idx + 1)),
+            };
+            let error = |e: String| format!("line {}: {}", idx + 1, e);
+            match key {
+                "name" => name = String::from(value),
+                "goal" => goal = Some(Goal::parse(value).map_err(error)?),
+                "pieces" => pieces = Some(parse_pieces(value).map_err(error)?),
+                "hold" => {
+                    hold = Some(
+                        tbp::parse_piece(value)
+                            .ok_or_else(|| error(format!("unknown piece `{}`", value)))?,
+                    )
+                }
+                "fumen" => fumen = Some(String::from(value)),
+                "row" => rows.push(parse_row(value).map_err(error)?),
+                _ => return Err(error(format!("unknown key `{}`", key))),
+            }
+        }
+
+        let mut puzzle = match (fumen, rows.is_empty()) {
+            (Some(_), false) => return Err(String::from("both a fumen and rows given")),
+            (Some(fumen), true) => Self::fumen_board(&name, &fumen)?,
+            (None, _) if rows.len() > MAX_ROWS => {
+                return Err(format!("more than {} rows", MAX_ROWS))
+            }
+            (None, _) => {
+                let mut matrix = [[None; 18]; 44];
+                for (y, row) in rows.iter().rev().enumerate() {
+                    matrix[4 + y][4..14].copy_from_slice(row);
+                }
+                Self {
+                    name,
+                    matrix,
+                    hold: None,
+                    pieces: Vec::new(),
+                    goal: Goal::PerfectClear,
+                }
+            }
+        };
+        if let Some(goal) = goal {
+            puzzle.goal = goal;
+        }
+        if let Some(pieces) = pieces {
+            puzzle.pieces = pieces;
+        }
+        if hold.is_some() {
+            puzzle.hold = hold;
+        }
+        if puzzle.pieces.is_empty() {
+            return Err(String::from("no pieces"));
+        }
+        Ok(puzzle)
+    }
+
+    // The first page of a fumen, with the pieces from its `#Q=[hold](current)next` comment if it has one,
+    // otherwise the piece on the page
+    pub fn from_fumen(name: &str, fumen: &str) -> Result<Self, String> {
+        let puzzle = Self::fumen_board(name, fumen)?;
+        if puzzle.pieces.is_empty() {
+            return Err(String::from("no pieces"));
+        }
+        Ok(puzzle)
+    }
+
+    // Like `from_fumen`, but leaves checking the pieces to the caller since a puzzle file can list them
+    fn fumen_board(name: &str, fumen: &str) -> Result<Self, String> {
+        let page = fumen::decode(fumen)?.swap_remove(0);
+        let mut matrix = [[None; 18]; 44];
+        for (y, row) in page.field.iter().enumerate() {
+            matrix[4 + y][4..14].copy_from_slice(row);
+        }
+        let (hold, mut pieces) = fumen::quiz(&page.comment).unwrap_or_default();
+        if pieces.is_empty() {
+            pieces.extend(page.piece.map(|piece| piece.tetrimino));
+        }
+        Ok(Self {
+            name: String::from(name),
+            matrix,
+            hold,
+            pieces,
+            goal: Goal::PerfectClear,
+        })
+    }
+
+    // A game on the puzzle's board that ends with its last piece, the first piece still has to spawn
+    pub fn game(&self) -> GameData {
+        let mut data = GameData::new(0);
+        data.endless = false;
+        data.queue = self.pieces.iter().copied().collect();
+        data.hold = self.hold;
+        data.matrix = self.matrix;
+        data
+    }
+
+    // Whether the lock that cleared `clear` decided the puzzle
+    pub fn check(&self, data: &GameData, clear: Option<&Clear>) -> Option<Outcome> {
+        let solved = match (self.goal, clear) {
+            (Goal::Lines(lines), _) => data.stats.lines >= lines,
+            (Goal::PerfectClear, Some(clear)) => clear.perfect,
+            (Goal::TSpinDouble, Some(clear)) => clear.spin == Spin::Full && clear.lines == 2,
+            (_, None) => false,
+        };
+        if solved {
+            Some(Outcome::Solved)
+        } else if data.state == GameState::Die {
+            Some(Outcome::Failed)
+        } else {
+            None
+        }
+    }
+}
+
+fn parse_pieces(value: &str) -> Result<Vec<Tetrimino>, String> {
+    value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| {
+            tbp::parse_piece(&format!("{}", c)).ok_or_else(|| format!("unknown piece `{}`", c))
+        })
+        .collect()
+}
+
+// Ten cells, `.` for empty, `X` for garbage or the letter of a piece
+fn parse_row(value: &str) -> Result<[Option<Tetrimino>; 10], String> {
+    let mut row = [None; 10];
+    if value.chars().count() != row.len() {
+        return Err(format!("row `{}` is not 10 cells wide", value));
+    }
+    for (cell, c) in row.iter_mut().zip(value.chars()) {
+        *cell = match c {
+            '.' => None,
+            'X' => Some(Tetrimino::Garbage),
+            _ => Some(
+                tbp::parse_piece(&format!("{}", c))
+                    .ok_or_else(|| format!("unknown cell `{}`", c))?,
+            ),
+        };
+    }
+    Ok(row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fumen::{Page, Piece};
+    use alloc::vec;
+
+    // A fumen with garbage in the bottom row but the right column
+    fn fumen(piece: Option<Tetrimino>, comment: &str) -> String {
+        let mut data = GameData::new(0);
+        data.matrix[4][4..13].copy_from_slice(&[Some(Tetrimino::Garbage); 9]);
+        let mut page = Page::from_game(&data, 0);
+        page.piece = piece.map(|tetrimino| Piece {
+            tetrimino,
+            rot: 1,
+            x: 9,
+            y: 2,
+        });
+        page.comment = String::from(comment);
+        fumen::encode(&[page])
+    }
+
+    fn error(src: &str) -> Option<String> {
+        Puzzle::parse("file", src).err()
+    }
+
+    #[test]
+    fn missing_pieces() {
+        assert_eq!(error("row = .........X"), Some(String::from("no pieces")));
+        assert_eq!(
+            error("goal = pc\npieces ="),
+            Some(String::from("no pieces"))
+        );
+        assert_eq!(
+            error(&format!("fumen = {}", fumen(None, ""))),
+            Some(String::from("no pieces"))
+        );
+        assert_eq!(error(&fumen(None, "")), Some(String::from("no pieces")));
+        assert_eq!(
+            error("row = ..........\npieces = TQ"),
+            Some(String::from("line 2: unknown piece `Q`"))
+        );
+    }
+
+    #[test]
+    fn fumen_only_file() {
+        let src = format!("https://fumen.zui.jp/?{}\n", fumen(Some(Tetrimino::I), ""));
+        let puzzle = Puzzle::parse("file", &src).ok().unwrap();
+        assert_eq!(puzzle.name, "file");
+        assert_eq!(puzzle.goal, Goal::PerfectClear);
+        assert_eq!(puzzle.pieces, [Tetrimino::I]);
+        assert_eq!(puzzle.hold, None);
+        assert_eq!(puzzle.matrix[4][4..13], [Some(Tetrimino::Garbage); 9]);
+        assert_eq!(puzzle.matrix[4][13], None);
+        assert!(puzzle.matrix[5..]
+            .iter()
+            .all(|row| row.iter().all(Option::is_none)));
+    }
+
+    #[test]
+    fn quiz_hold_and_queue() {
+        let quiz = fumen(Some(Tetrimino::I), "#Q=[L](T)IO");
+        let puzzle = Puzzle::parse("file", &quiz).ok().unwrap();
+        assert_eq!(puzzle.hold, Some(Tetrimino::L));
+        assert_eq!(puzzle.pieces, [Tetrimino::T, Tetrimino::I, Tetrimino::O]);
+
+        // Keys in a puzzle file win over the quiz
+        let src = format!("name = Quiz\nfumen = {}\ngoal = lines 1\nhold = S\n", quiz);
+        let puzzle = Puzzle::parse("file", &src).ok().unwrap();
+        assert_eq!(puzzle.name, "Quiz");
+        assert_eq!(puzzle.goal, Goal::Lines(1));
+        assert_eq!(puzzle.hold, Some(Tetrimino::S));
+        assert_eq!(puzzle.pieces, [Tetrimino::T, Tetrimino::I, Tetrimino::O]);
+        let puzzle = Puzzle::parse("file", &format!("fumen = {}\npieces = Z", quiz))
+            .ok()
+            .unwrap();
+        assert_eq!(puzzle.pieces, [Tetrimino::Z]);
+        assert_eq!(puzzle.hold, Some(Tetrimino::L));
+    }
+
+    #[test]
+    fn check_outcomes() {
+        let puzzle = |goal| Puzzle {
+            name: String::new(),
+            matrix: [[None; 18]; 44],
+            hold: None,
+            pieces: vec![Tetrimino::T],
+            goal,
+        };
+        let clear = |lines, spin, perfect| Clear {
+            lines,
+            spin,
+            back_to_back: false,
+            combo: 0,
+            perfect,
+        };
+        let mut data = puzzle(Goal::PerfectClear).game();
+        data.state = GameState::Drop;
+        let tsd = clear(2, Spin::Full, false);
+        let pc = clear(4, Spin::None, true);
+
+        assert_eq!(puzzle(Goal::PerfectClear).check(&data, None), None);
+        assert_eq!(puzzle(Goal::PerfectClear).check(&data, Some(&tsd)), None);
+        assert_eq!(
+            puzzle(Goal::PerfectClear).check(&data, Some(&pc)),
+            Some(Outcome::Solved)
+        );
+        assert_eq!(
+            puzzle(Goal::TSpinDouble).check(&data, Some(&tsd)),
+            Some(Outcome::Solved)
+        );
+        let tss = clear(1, Spin::Full, false);
+        let mini = clear(2, Spin::Mini, false);
+        assert_eq!(puzzle(Goal::TSpinDouble).check(&data, Some(&tss)), None);
+        assert_eq!(puzzle(Goal::TSpinDouble).check(&data, Some(&mini)), None);
+
+        // Lines count over the whole game
+        data.stats.lines = 2;
+        assert_eq!(puzzle(Goal::Lines(3)).check(&data, None), None);
+        data.stats.lines = 3;
+        assert_eq!(
+            puzzle(Goal::Lines(3)).check(&data, None),
+            Some(Outcome::Solved)
+        );
+
+        // Running out of pieces fails, unless the last one solved it
+        data.state = GameState::Die;
+        assert_eq!(
+            puzzle(Goal::PerfectClear).check(&data, Some(&tsd)),
+            Some(Outcome::Failed)
+        );
+        assert_eq!(
+            puzzle(Goal::PerfectClear).check(&data, Some(&pc)),
+            Some(Outcome::Solved)
+        );
+    }
+}
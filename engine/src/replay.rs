@@ -0,0 +1,223 @@
+use crate::game::{Clear, GameData, GameState};
+use crate::settings::{Action, ACTIONS};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// First line of every replay file
+const HEADER: &str = "rustris replay 1";
+
+// Everything that changes the game, timers included, so replays don't depend on timing
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Input {
+    Action(Action),
+    Gravity, // the piece falls a row
+    Lock,    // lock delay ran out or the piece was hard dropped
+}
+
+impl Input {
+    pub fn name(self) -> &'static str {
+        match self {
+            Input::Action(action) => {
+                let (_, name) = ACTIONS.iter().find(|(a, _)| *a == action).unwrap();
+                &name[4..]
+            }
+            Input::Gravity => "gravity",
+            Input::Lock => "lock",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "gravity" => Some(Input::Gravity),
+            "lock" => Some(Input::Lock),
+            _ => ACTIONS
+                .iter()
+                .find(|(_, n)| &n[4..] == name)
+                .map(|(action, _)| Input::Action(*action)),
+        }
+    }
+}
+
+// A game as its seed and every input with the game time (ms) it happened at
+#[derive(Clone, Default)]
+pub struct Replay {
+    pub seed: u64,
+    pub inputs: Vec<(u64, Input)>,
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, time: u64, input: Input) {
+        self.inputs.push((time, input));
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut out = format!("{}\nseed {}\n", HEADER, self.seed);
+        for (time, input) in self.inputs.iter() {
+            out += &format!("{} {}\n", time, input.name());
+        }
+        out
+    }
+
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut lines = src.lines().enumerate();
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            _ => return Err(String::from("not a replay")),
+        }
+        let seed = match lines.next() {
+            Some((_, line)) if line.starts_with("seed ") => line[5..]
+                .parse()
+                .map_err(|_| String::from("line 2: invalid seed"))?,
+            _ => return Err(String::from("line 2: expected `seed`")),
+        };
+
+        let mut replay = Self::new(seed);
+        for (idx, line) in lines {
+            let mut parts = line.split_whitespace();
+            let time = parts.next().and_then(|t| t.parse().ok());
+            let input = parts.next().and_then(Input::parse);
+            match (time, input, parts.next()) {
+                (Some(time), Some(input), None) => replay.record(time, input),
+                _ => return Err(format!("line {}: expected `<time> <input>`", idx + 1)),
+            }
+        }
+        Ok(replay)
+    }
+}
+
+// Takes the next piece from the queue, moves to `Die` if it is blocked or there is none left
+pub fn spawn(data: &mut GameData) {
+    if data.queue.is_empty() {
+        data.state = GameState::Die;
+        return;
+    }
+    let tetrimino = data.get_piece();
+    data.state = if tetrimino.spawn(data) {
+        GameState::Die
+    } else {
+        GameState::Drop
+    };
+}
+
+// The rules without any timers, returns what a lock cleared
+pub fn apply(data: &mut GameData, input: Input) -> Option<Clear> {
+    if data.state != GameState::Drop {
+        return None;
+    }
+    let (tetrimino, x, y, rot) = data.active_mino;
+    if let Input::Action(Action::Left)
+    | Input::Action(Action::Right)
+    | Input::Action(Action::RotateCw)
+    | Input::Action(Action::RotateCcw) = input
+    {
+        data.moves += 1;
+    }
+    match input {
+        Input::Action(Action::Left) => shift(data, (x - 1, y)),
+        Input::Action(Action::Right) => shift(data, (x + 1, y)),
+        Input::Action(Action::SoftDrop) | Input::Gravity => shift(data, (x, y - 1)),
+        Input::Action(Action::HardDrop) => {
+            // Dropping zero rows keeps a spin
+            let ghost = data.ghost();
+            if ghost != (x, y) {
+                shift(data, ghost);
+            }
+        }
+        Input::Action(Action::RotateCw) => {
+            tetrimino.rotate(data, (x, y), rot, (rot + 1) % 4);
+        }
+        Input::Action(Action::RotateCcw) => {
+            tetrimino.rotate(data, (x, y), rot, (rot + 3) % 4);
+        }
+        Input::Action(Action::Hold) => {
+            if data.hold_flag {
+                return None;
+            }
+            let next = match data.hold {
+                Some(held) => held,
+                None if data.queue.is_empty() => return None,
+                None => data.get_piece(),
+            };
+            next.spawn(data);
+            data.hold = Some(tetrimino);
+            data.hold_flag = true;
+        }
+        Input::Lock => {
+            let locked_out = data.locked_out();
+            data.stats.lock(tetrimino);
+            data.lock();
+            if locked_out {
+                data.state = GameState::Die;
+                return None;
+            }
+            let rows = data.full_rows();
+            let clear = data.score_clear(&rows);
+            data.clear_rows(&rows);
+            data.stats.clear(rows.len());
+            spawn(data);
+            return Some(clear);
+        }
+    }
+    None
+}
+
+fn shift(data: &mut GameData, pos: (usize, usize)) {
+    let (tetrimino, _, _, rot) = data.active_mino;
+    if !tetrimino.collides(&data.matrix, pos, rot) {
+        data.active_mino = (tetrimino, pos.0, pos.1, rot);
+        data.rotated = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut replay = Replay::new(u64::MAX);
+        replay.record(0, Input::Gravity);
+        for (idx, (action, _)) in ACTIONS.iter().enumerate() {
+            replay.record(100 * idx as u64, Input::Action(*action));
+        }
+        replay.record(5000, Input::Lock);
+
+        let parsed = Replay::parse(&replay.serialize()).ok().unwrap();
+        assert_eq!(parsed.seed, replay.seed);
+        assert_eq!(parsed.inputs, replay.inputs);
+        assert_eq!(parsed.serialize(), replay.serialize());
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let error = |src: &str| Replay::parse(src).err();
+        assert_eq!(error(""), Some(String::from("not a replay")));
+        assert_eq!(
+            error("rustris replay 2\nseed 1\n"),
+            Some(String::from("not a replay"))
+        );
+        assert_eq!(
+            error("rustris replay 1\n"),
+            Some(String::from("line 2: expected `seed`"))
+        );
+        assert_eq!(
+            error("rustris replay 1\nseed -1\n"),
+            Some(String::from("line 2: invalid seed"))
+        );
+
+        let body = |lines: &str| error(&format!("rustris replay 1\nseed 1\n0 gravity\n{}", lines));
+        assert_eq!(body(""), None);
+        let expected = Some(String::from("line 4: expected `<time> <input>`"));
+        for line in ["10", "10 teleport", "x lock", "10 lock now", "-5 lock"].iter() {
+            assert_eq!(body(line), expected, "{}", line);
+        }
+    }
+}
@@ -0,0 +1,673 @@
+use crate::game::PREVIEW_MAX;
+use crate::garbage::{Counter, Holes, Rules, ATTACK_TABLES};
+use crate::layout::{Hand, BLOCK_SIZES};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+pub const FILE_NAME: &str = "rustris.cfg";
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum KeyCode {
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Escape,
+}
+
+impl KeyCode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "up" => Some(KeyCode::Up),
+            "down" => Some(KeyCode::Down),
+            "left" => Some(KeyCode::Left),
+            "right" => Some(KeyCode::Right),
+            "space" => Some(KeyCode::Char(' ')),
+            "enter" => Some(KeyCode::Char('\r')),
+            "tab" => Some(KeyCode::Char('\t')),
+            "backspace" => Some(KeyCode::Char('\u{8}')),
+            "escape" => Some(KeyCode::Escape),
+            _ => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) if c.is_ascii_graphic() => Some(KeyCode::Char(c)),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    // Escape opens the menu, and keys without a name in the config file couldn't be saved
+    pub fn bindable(self) -> bool {
+        self != KeyCode::Escape && KeyCode::parse(&self.name()) == Some(self)
+    }
+
+    // `parse` reads back the name of every key that can be bound
+    pub fn name(self) -> String {
+        match self {
+            KeyCode::Char(' ') => "space".to_string(),
+            KeyCode::Char('\r') => "enter".to_string(),
+            KeyCode::Char('\t') => "tab".to_string(),
+            KeyCode::Char('\u{8}') => "backspace".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Up => "up".to_string(),
+            KeyCode::Down => "down".to_string(),
+            KeyCode::Left => "left".to_string(),
+            KeyCode::Right => "right".to_string(),
+            KeyCode::Escape => "escape".to_string(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Action {
+    Left,
+    Right,
+    SoftDrop,
+    HardDrop,
+    RotateCw,
+    RotateCcw,
+    Hold,
+}
+
+pub const ACTIONS: [(Action, &str); 7] = [
+    (Action::Left, "key_left"),
+    (Action::Right, "key_right"),
+    (Action::SoftDrop, "key_soft_drop"),
+    (Action::HardDrop, "key_hard_drop"),
+    (Action::RotateCw, "key_rotate_cw"),
+    (Action::RotateCcw, "key_rotate_ccw"),
+    (Action::Hold, "key_hold"),
+];
+
+// Player 2's bindings in versus, indexed like ACTIONS
+const PLAYER_2_KEYS: [&str; 7] = [
+    "p2_key_left",
+    "p2_key_right",
+    "p2_key_soft_drop",
+    "p2_key_hard_drop",
+    "p2_key_rotate_cw",
+    "p2_key_rotate_ccw",
+    "p2_key_hold",
+];
+// Both key maps
+const KEY_COUNT: usize = 2 * ACTIONS.len();
+
+// ms per row, the same in the file and the menu
+const FALL_SPEED_MIN: u64 = 50;
+const FALL_SPEED_MAX: u64 = 10000;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Renderer {
+    Blt,
+    Linear, // Writes straight to the GOP frame buffer
+}
+
+impl Renderer {
+    pub fn name(self) -> &'static str {
+        match self {
+            Renderer::Blt => "blt",
+            Renderer::Linear => "linear",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Mode {
+    Marathon,
+    Versus,   // two players on one keyboard
+    Network,  // against another machine on the same network
+    Survival, // alone against garbage that keeps coming
+    Puzzle,   // boards from the puzzles directory
+}
+
+const MODES: [Mode; 5] = [
+    Mode::Marathon,
+    Mode::Versus,
+    Mode::Network,
+    Mode::Survival,
+    Mode::Puzzle,
+];
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Marathon => "marathon",
+            Mode::Versus => "versus",
+            Mode::Network => "network",
+            Mode::Survival => "survival",
+            Mode::Puzzle => "puzzle",
+        }
+    }
+}
+
+// How well the CPU opponent plays
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+const DIFFICULTIES: [Option<Difficulty>; 4] = [
+    None,
+    Some(Difficulty::Easy),
+    Some(Difficulty::Normal),
+    Some(Difficulty::Hard),
+];
+
+fn cpu_name(cpu: Option<Difficulty>) -> &'static str {
+    match cpu {
+        None => "off",
+        Some(Difficulty::Easy) => "easy",
+        Some(Difficulty::Normal) => "normal",
+        Some(Difficulty::Hard) => "hard",
+    }
+}
+
+// Where the game is shown, only read at startup
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Display {
+    Auto,   // graphics if available, the text console otherwise
+    Text,   // the firmware text console
+    Serial, // ANSI escape sequences over the first serial port
+}
+
+impl Display {
+    pub fn name(self) -> &'static str {
+        match self {
+            Display::Auto => "auto",
+            Display::Text => "text",
+            Display::Serial => "serial",
+        }
+    }
+}
+
+// Menu item that opens the video mode picker
+pub const VIDEO_MODE_ITEM: usize = 2;
+pub const SKIN_ITEM: usize = 4;
+const FIRST_KEY_ITEM: usize = 15;
+
+// Name of the skin built into the binary
+pub const DEFAULT_SKIN: &str = "default";
+// Name of the procedural skin with plain blocks
+pub const FLAT_SKIN: &str = "flat";
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Settings {
+    pub fall_speed: u64, // ms per row
+    pub lock_delay: u64, // ms
+    pub width: u32,      // px, 0 keeps the firmware's video mode
+    pub height: u32,     // px, 0 keeps the firmware's video mode
+    pub renderer: Renderer,
+    pub display: Display,
+    pub skin: String, // directory in skins/
+    pub block: u32,   // px, 0 fits the screen
+    pub hand: Hand,
+    pub animations: bool,
+    pub previews: usize, // pieces shown in the next queue
+    pub mode: Mode,
+    pub cpu: Option<Difficulty>, // plays player 2 in versus
+    pub garbage: Rules,
+    pub keys: [KeyCode; KEY_COUNT], // indexed like ACTIONS, then again for player 2
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            fall_speed: 1000,
+            lock_delay: 500,
+            width: 0,
+            height: 0,
+            renderer: Renderer::Blt,
+            display: Display::Auto,
+            skin: String::from(DEFAULT_SKIN),
+            block: 0,
+            hand: Hand::Right,
+            animations: true,
+            previews: 5,
+            mode: Mode::Marathon,
+            cpu: None,
+            garbage: Rules::default(),
+            keys: [
+                KeyCode::Left,
+                KeyCode::Right,
+                KeyCode::Down,
+                KeyCode::Char(' '),
+                KeyCode::Char('e'),
+                KeyCode::Char('q'),
+                KeyCode::Char('f'),
+                KeyCode::Char('j'),
+                KeyCode::Char('l'),
+                KeyCode::Char('k'),
+                KeyCode::Char('i'),
+                KeyCode::Char('o'),
+                KeyCode::Char('u'),
+                KeyCode::Char('h'),
+            ],
+        }
+    }
+}
+
+impl Settings {
+    // Player 1's action for `key`
+    pub fn action(&self, key: KeyCode) -> Option<Action> {
+        match self.player_action(key) {
+            Some((0, action)) => Some(action),
+            _ => None,
+        }
+    }
+
+    // Which player's action `key` is bound to
+    pub fn player_action(&self, key: KeyCode) -> Option<(usize, Action)> {
+        self.keys
+            .iter()
+            .position(|k| *k == key)
+            .map(|idx| (idx / ACTIONS.len(), ACTIONS[idx % ACTIONS.len()].0))
+    }
+
+    // Parses `key = value` lines, falling back to the default for every invalid entry
+    pub fn parse(src: &str) -> (Self, Vec<String>) {
+        let mut settings = Self::default();
+        let mut errors = Vec::new();
+
+        for (idx, line) in src.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = match line.find('=') {
+                Some(pos) => (line[..pos].trim(), line[pos + 1..].trim()),
+                None => {
+                    errors.push(format!("line {}: expected `key = value`", idx + 1));
+                    continue;
+                }
+            };
+
+            if let Err(e) = settings.set(key, value) {
+                errors.push(format!("line {}: {}", idx + 1, e));
+            }
+        }
+
+        for (i, key) in settings.keys.iter().enumerate() {
+            if settings.keys[..i].contains(key) {
+                errors.push(format!("`{}` is bound twice, using defaults", key.name()));
+                settings.keys = Self::default().keys;
+                break;
+            }
+        }
+
+        (settings, errors)
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "fall_speed" => self.fall_speed = parse_num(value, FALL_SPEED_MIN, FALL_SPEED_MAX)?,
+            "lock_delay" => self.lock_delay = parse_num(value, 0, 5000)?,
+            "width" => self.width = parse_resolution(value, 640, 3840)?,
+            "height" => self.height = parse_resolution(value, 480, 2160)?,
+            "renderer" => {
+                self.renderer = match value {
+                    "blt" => Renderer::Blt,
+                    "linear" => Renderer::Linear,
+                    _ => return Err(format!("unknown renderer `{}`", value)),
+                }
+            }
+            "display" => {
+                self.display = match value {
+                    "auto" => Display::Auto,
+                    "text" => Display::Text,
+                    "serial" => Display::Serial,
+                    _ => return Err(format!("unknown display `{}`", value)),
+                }
+            }
+            "skin" => {
+                if value.is_empty() || value.contains(['/', '\\', '.']) {
+                    return Err(format!("invalid skin name `{}`", value));
+                }
+                self.skin = String::from(value);
+            }
+            "block" => {
+                self.block = match value {
+                    "auto" => 0,
+                    _ => match value.parse() {
+                        Ok(size) if BLOCK_SIZES.contains(&size) => size,
+                        _ => return Err(format!("invalid block size `{}`", value)),
+                    },
+                }
+            }
+            "layout" => {
+                self.hand = match value {
+                    "right" => Hand::Right,
+                    "left" => Hand::Left,
+                    _ => return Err(format!("unknown layout `{}`", value)),
+                }
+            }
+            "previews" => self.previews = parse_num(value, 0, PREVIEW_MAX as u64)? as usize,
+            "mode" => {
+                self.mode = match value {
+                    "marathon" => Mode::Marathon,
+                    "versus" => Mode::Versus,
+                    "network" => Mode::Network,
+                    "survival" => Mode::Survival,
+                    "puzzle" => Mode::Puzzle,
+                    _ => return Err(format!("unknown mode `{}`", value)),
+                }
+            }
+            "cpu" => {
+                self.cpu = *DIFFICULTIES
+                    .iter()
+                    .find(|cpu| cpu_name(**cpu) == value)
+                    .ok_or_else(|| format!("unknown cpu difficulty `{}`", value))?;
+            }
+            "attack" => {
+                self.garbage.table = *ATTACK_TABLES
+                    .iter()
+                    .find(|table| table.name() == value)
+                    .ok_or_else(|| format!("unknown attack table `{}`", value))?;
+            }
+            "garbage_delay" => self.garbage.delay = parse_num(value, 0, 5000)?,
+            "counter" => {
+                self.garbage.counter = match value {
+                    "cancel" => Counter::Cancel,
+                    "offset" => Counter::Offset,
+                    _ => return Err(format!("unknown counter rule `{}`", value)),
+                }
+            }
+            "holes" => {
+                self.garbage.holes = match value {
+                    "clean" => Holes::Clean,
+                    "messy" => Holes::Messy,
+                    _ => return Err(format!("unknown hole pattern `{}`", value)),
+                }
+            }
+            "animations" => {
+                self.animations = match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err(format!("expected `on` or `off`, got `{}`", value)),
+                }
+            }
+            _ => {
+                let idx = (0..KEY_COUNT)
+                    .position(|idx| key_name(idx) == key)
+                    .ok_or_else(|| format!("unknown setting `{}`", key))?;
+                self.keys[idx] = match KeyCode::parse(value) {
+                    Some(code) if code.bindable() => code,
+                    Some(_) => return Err(format!("`{}` can't be bound", value)),
+                    None => return Err(format!("invalid key `{}`", value)),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut out = String::from("# Rustris settings\n");
+        out += &format!("fall_speed = {}\n", self.fall_speed);
+        out += &format!("lock_delay = {}\n", self.lock_delay);
+        out += &format!("width = {}\n", resolution_name(self.width));
+        out += &format!("height = {}\n", resolution_name(self.height));
+        out += &format!("renderer = {}\n", self.renderer.name());
+        out += &format!("display = {}\n", self.display.name());
+        out += &format!("skin = {}\n", self.skin);
+        out += &format!("block = {}\n", self.block_name());
+        out += &format!("layout = {}\n", self.hand.name());
+        out += &format!("animations = {}\n", on_off(self.animations));
+        out += &format!("previews = {}\n", self.previews);
+        out += &format!("mode = {}\n", self.mode.name());
+        out += &format!("cpu = {}\n", cpu_name(self.cpu));
+        out += &format!("attack = {}\n", self.garbage.table.name());
+        out += &format!("garbage_delay = {}\n", self.garbage.delay);
+        out += &format!("counter = {}\n", self.garbage.counter.name());
+        out += &format!("holes = {}\n", self.garbage.holes.name());
+        for (idx, key) in self.keys.iter().enumerate() {
+            out += &format!("{} = {}\n", key_name(idx), key.name());
+        }
+        out
+    }
+
+    pub fn items(&self) -> Vec<(String, String)> {
+        let mut items: Vec<(String, String)> = vec![
+            ("Fall speed", format!("{} ms", self.fall_speed)),
+            ("Lock delay", format!("{} ms", self.lock_delay)),
+            ("Video mode", self.video_mode_name()),
+            ("Renderer", String::from(self.renderer.name())),
+            ("Skin", self.skin.clone()),
+            ("Block size", self.block_name()),
+            ("Layout", format!("{}-handed", self.hand.name())),
+            ("Animations", String::from(on_off(self.animations))),
+            ("Next pieces", format!("{}", self.previews)),
+            ("Mode", String::from(self.mode.name())),
+            ("CPU", String::from(cpu_name(self.cpu))),
+            ("Attack", String::from(self.garbage.table.name())),
+            ("Garbage delay", format!("{} ms", self.garbage.delay)),
+            ("Counter", String::from(self.garbage.counter.name())),
+            ("Holes", String::from(self.garbage.holes.name())),
+        ]
+        .into_iter()
+        .map(|(name, value)| (String::from(name), value))
+        .collect();
+        for (idx, key) in self.keys.iter().enumerate() {
+            let name = &ACTIONS[idx % ACTIONS.len()].1[4..];
+            let label = match idx / ACTIONS.len() {
+                0 => String::from(name),
+                player => format!("P{} {}", player + 1, name),
+            };
+            items.push((label, key.name()));
+        }
+        items
+    }
+
+    // Index of the key binding edited by menu item `item`, if any
+    pub fn binding(item: usize) -> Option<usize> {
+        item.checked_sub(FIRST_KEY_ITEM)
+            .filter(|idx| *idx < KEY_COUNT)
+    }
+
+    pub fn adjust(&mut self, item: usize, delta: i64) {
+        match item {
+            0 => {
+                self.fall_speed = step(self.fall_speed, delta * 50, FALL_SPEED_MIN, FALL_SPEED_MAX)
+            }
+            1 => self.lock_delay = step(self.lock_delay, delta * 50, 0, 5000),
+            3 => {
+                self.renderer = match self.renderer {
+                    Renderer::Blt => Renderer::Linear,
+                    Renderer::Linear => Renderer::Blt,
+                }
+            }
+            5 => {
+                // Cycles through auto and the fixed sizes
+                let idx = match BLOCK_SIZES.iter().position(|b| *b == self.block) {
+                    Some(idx) => idx as i64 + 1,
+                    None => 0,
+                };
+                let count = BLOCK_SIZES.len() as i64 + 1;
+                self.block = match (idx + delta).rem_euclid(count) {
+                    0 => 0,
+                    idx => BLOCK_SIZES[idx as usize - 1],
+                };
+            }
+            6 => {
+                self.hand = match self.hand {
+                    Hand::Right => Hand::Left,
+                    Hand::Left => Hand::Right,
+                }
+            }
+            7 => self.animations = !self.animations,
+            8 => self.previews = step(self.previews as u64, delta, 0, PREVIEW_MAX as u64) as usize,
+            9 => {
+                let idx = MODES.iter().position(|mode| *mode == self.mode).unwrap() as i64;
+                self.mode = MODES[(idx + delta).rem_euclid(MODES.len() as i64) as usize];
+            }
+            10 => {
+                let idx = DIFFICULTIES
+                    .iter()
+                    .position(|cpu| *cpu == self.cpu)
+                    .unwrap() as i64;
+                self.cpu =
+                    DIFFICULTIES[(idx + delta).rem_euclid(DIFFICULTIES.len() as i64) as usize];
+            }
+            11 => {
+                let table = self.garbage.table;
+                let idx = ATTACK_TABLES.iter().position(|t| *t == table).unwrap() as i64;
+                self.garbage.table =
+                    ATTACK_TABLES[(idx + delta).rem_euclid(ATTACK_TABLES.len() as i64) as usize];
+            }
+            12 => self.garbage.delay = step(self.garbage.delay, delta * 100, 0, 5000),
+            13 => {
+                self.garbage.counter = match self.garbage.counter {
+                    Counter::Cancel => Counter::Offset,
+                    Counter::Offset => Counter::Cancel,
+                }
+            }
+            14 => {
+                self.garbage.holes = match self.garbage.holes {
+                    Holes::Clean => Holes::Messy,
+                    Holes::Messy => Holes::Clean,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn block_name(&self) -> String {
+        match self.block {
+            0 => String::from("auto"),
+            size => format!("{}", size),
+        }
+    }
+
+    fn video_mode_name(&self) -> String {
+        match (self.width, self.height) {
+            (0, _) | (_, 0) => String::from("auto"),
+            (width, height) => format!("{}x{}", width, height),
+        }
+    }
+
+    pub fn bind(&mut self, binding: usize, key: KeyCode) {
+        if !key.bindable() {
+            return;
+        }
+        // Swap with an existing binding so every key stays unique
+        if let Some(old) = self.keys.iter().position(|k| *k == key) {
+            self.keys[old] = self.keys[binding];
+        }
+        self.keys[binding] = key;
+    }
+}
+
+// Config name of binding `idx`
+fn key_name(idx: usize) -> &'static str {
+    match idx.checked_sub(ACTIONS.len()) {
+        None => ACTIONS[idx].1,
+        Some(idx) => PLAYER_2_KEYS[idx],
+    }
+}
+
+fn parse_num(value: &str, min: u64, max: u64) -> Result<u64, String> {
+    match value.parse::<u64>() {
+        Ok(num) if (min..=max).contains(&num) => Ok(num),
+        Ok(_) => Err(format!("`{}` is out of range ({}-{})", value, min, max)),
+        Err(_) => Err(format!("`{}` is not a number", value)),
+    }
+}
+
+fn parse_resolution(value: &str, min: u64, max: u64) -> Result<u32, String> {
+    match value {
+        "auto" => Ok(0),
+        _ => Ok(parse_num(value, min, max)? as u32),
+    }
+}
+
+fn resolution_name(value: u32) -> String {
+    match value {
+        0 => String::from("auto"),
+        px => format!("{}", px),
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn step(value: u64, delta: i64, min: u64, max: u64) -> u64 {
+    (value as i64 + delta).max(min as i64).min(max as i64) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::garbage::AttackTable;
+
+    #[test]
+    fn serialize_round_trip() {
+        let mut keys = Settings::default().keys;
+        keys[0] = KeyCode::Char('\t');
+        keys[7] = KeyCode::Char('\r');
+        let settings = Settings {
+            fall_speed: 350,
+            lock_delay: 0,
+            width: 1024,
+            height: 768,
+            renderer: Renderer::Linear,
+            display: Display::Serial,
+            skin: String::from(FLAT_SKIN),
+            block: 24,
+            hand: Hand::Left,
+            animations: false,
+            previews: 0,
+            mode: Mode::Survival,
+            cpu: Some(Difficulty::Hard),
+            garbage: Rules {
+                table: AttackTable::Tetrio,
+                delay: 500,
+                counter: Counter::Offset,
+                holes: Holes::Messy,
+            },
+            keys,
+        };
+        for settings in [Settings::default(), settings].iter() {
+            let (parsed, errors) = Settings::parse(&settings.serialize());
+            assert_eq!(errors, Vec::<String>::new());
+            assert_eq!(&parsed, settings);
+        }
+    }
+
+    #[test]
+    fn fall_speed_bounds_match_the_menu() {
+        let (settings, errors) = Settings::parse("fall_speed = 1");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(settings.fall_speed, Settings::default().fall_speed);
+
+        let mut settings = Settings::parse("fall_speed = 50").0;
+        settings.adjust(0, -1);
+        assert_eq!(settings.fall_speed, FALL_SPEED_MIN);
+        settings.fall_speed = FALL_SPEED_MAX;
+        settings.adjust(0, 1);
+        assert_eq!(settings.fall_speed, FALL_SPEED_MAX);
+    }
+
+    #[test]
+    fn escape_is_never_bound() {
+        let (settings, errors) = Settings::parse("key_hold = escape");
+        assert_eq!(errors, ["line 1: `escape` can't be bound"]);
+        assert_eq!(settings.keys, Settings::default().keys);
+
+        let mut settings = Settings::default();
+        settings.bind(0, KeyCode::Escape);
+        assert_eq!(settings.keys, Settings::default().keys);
+        settings.bind(0, KeyCode::Char('e'));
+        assert_eq!(settings.keys[0], KeyCode::Char('e'));
+        assert_eq!(settings.keys[4], KeyCode::Left);
+    }
+}
@@ -0,0 +1,284 @@
+use crate::game::Tetrimino;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use embedded_graphics::image::Image;
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::*;
+use log::warn;
+use tinybmp::Bmp;
+
+// Edge length of the procedural and embedded sprites in pixels
+pub const BLOCK: u32 = 16;
+
+pub enum Sprite {
+    Tetrimino(Tetrimino),
+    Wall,
+    Empty,
+}
+
+// Names of the cells in a sprite sheet manifest, indexed like ASSETS and then the procedural sprites
+const CELLS: [&str; 11] = [
+    "o", "i", "t", "l", "j", "s", "z", "ghost", "wall", "empty", "garbage",
+];
+
+// A decoded square image, scaled to the block size when drawn
+pub struct Tile {
+    size: u32,
+    pixels: Vec<Rgb888>,
+}
+
+impl Tile {
+    fn from_fn(f: impl Fn(u32, u32) -> Rgb888) -> Self {
+        let mut pixels = Vec::with_capacity((BLOCK * BLOCK) as usize);
+        for y in 0..BLOCK {
+            for x in 0..BLOCK {
+                pixels.push(f(x, y));
+            }
+        }
+        Self {
+            size: BLOCK,
+            pixels,
+        }
+    }
+
+    // The `size` sized square at `pos` of `image`
+    fn cut(image: &Bitmap, pos: (u32, u32), size: u32) -> Self {
+        let mut pixels = Vec::with_capacity((size * size) as usize);
+        for y in pos.1..pos.1 + size {
+            let row = (y * image.width) as usize;
+            pixels.extend_from_slice(&image.pixels[row + pos.0 as usize..][..size as usize]);
+        }
+        Self { size, pixels }
+    }
+
+    fn from_bmp(data: &[u8]) -> Option<Self> {
+        let image = Bitmap::decode(data)?;
+        if image.width != image.height {
+            return None;
+        }
+        Some(Self::cut(&image, (0, 0), image.width))
+    }
+
+    // Draws the tile as a `size` x `size` square, nearest-neighbour scaled
+    pub fn draw<D: DrawTarget<Rgb888>>(
+        &self,
+        target: &mut D,
+        pos: Point,
+        size: u32,
+    ) -> Result<(), D::Error> {
+        target.draw_iter((0..size * size).map(|idx| {
+            let (x, y) = (idx % size, idx / size);
+            let color =
+                self.pixels[(y * self.size / size * self.size + x * self.size / size) as usize];
+            Pixel(pos + Point::new(x as i32, y as i32), color)
+        }))
+    }
+}
+
+struct Bitmap {
+    width: u32,
+    height: u32,
+    pixels: Vec<Rgb888>,
+}
+
+impl Bitmap {
+    fn decode(data: &[u8]) -> Option<Self> {
+        let bmp = Bmp::from_slice(data).ok()?;
+        let (width, height) = (bmp.width(), bmp.height());
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        let mut pixels = vec![Rgb888::BLACK; (width * height) as usize];
+        let image: Image<Bmp, Rgb888> = Image::new(&bmp, Point::zero());
+        for Pixel(pos, color) in &image {
+            if (0..width as i32).contains(&pos.x) && (0..height as i32).contains(&pos.y) {
+                pixels[(pos.y as u32 * width + pos.x as u32) as usize] = color;
+            }
+        }
+        Some(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+macro_rules! init_sprites {
+    ($($file:literal),*) => {
+        const ASSETS: [(&str, &[u8]); 10] = [$(($file, include_bytes!(concat!("../../assets/", $file)))),*];
+    }
+}
+
+init_sprites!(
+    "o-piece.bmp",
+    "i-piece.bmp",
+    "t-piece.bmp",
+    "l-piece.bmp",
+    "j-piece.bmp",
+    "s-piece.bmp",
+    "z-piece.bmp",
+    "ghost.bmp",
+    "wall.bmp",
+    "empty.bmp"
+);
+
+pub struct Skin {
+    tiles: Vec<Tile>,
+}
+
+impl Skin {
+    // The sprites baked into the binary
+    pub fn embedded() -> Self {
+        let mut tiles: Vec<Tile> = ASSETS
+            .iter()
+            .enumerate()
+            .map(|(idx, (file, data))| {
+                Tile::from_bmp(data).unwrap_or_else(|| {
+                    warn!("Invalid sprite {}, using built-in skin", file);
+                    fallback(idx, bevelled)
+                })
+            })
+            .collect();
+        tiles.extend((ASSETS.len()..CELLS.len()).map(|idx| fallback(idx, bevelled)));
+        Self { tiles }
+    }
+
+    // The procedural skin with plain blocks instead of bevelled ones
+    pub fn flat() -> Self {
+        let tiles = (0..CELLS.len()).map(|idx| fallback(idx, flat)).collect();
+        Self { tiles }
+    }
+
+    // A single sprite sheet with square cells, laid out left to right, top to bottom.
+    // The manifest sets the cell size (`block = 16`) and which sprite each cell holds
+    // (`order = o i t l j s z ghost wall empty garbage`), sprites missing from it come from the built-in skin.
+    pub fn from_sheet(manifest: &str, sheet: &[u8]) -> Result<Self, String> {
+        let mut block = BLOCK;
+        // Sheets made before garbage existed leave it out
+        let mut order: Vec<usize> = (0..ASSETS.len()).collect();
+
+        for line in manifest.lines() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = match line.find('=') {
+                Some(pos) => (line[..pos].trim(), line[pos + 1..].trim()),
+                None => return Err(format!("expected `key = value`, got `{}`", line)),
+            };
+            match key {
+                "block" => {
+                    block = match value.parse() {
+                        Ok(size) if size > 0 => size,
+                        _ => return Err(format!("invalid block size `{}`", value)),
+                    }
+                }
+                "order" => {
+                    order = value
+                        .split_whitespace()
+                        .map(|name| {
+                            CELLS
+                                .iter()
+                                .position(|cell| *cell == name)
+                                .ok_or_else(|| format!("unknown cell `{}`", name))
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => return Err(format!("unknown key `{}`", key)),
+            }
+        }
+
+        let image = Bitmap::decode(sheet).ok_or_else(|| String::from("invalid sprite sheet"))?;
+        let columns = image.width / block;
+        let rows = image.height / block;
+        if (order.len() as u32) > columns * rows {
+            return Err(format!(
+                "sheet holds {} cells, manifest lists {}",
+                columns * rows,
+                order.len()
+            ));
+        }
+
+        let mut skin = Self::embedded();
+        for (cell, sprite) in order.iter().enumerate() {
+            let cell = cell as u32;
+            let pos = ((cell % columns) * block, (cell / columns) * block);
+            skin.tiles[*sprite] = Tile::cut(&image, pos, block);
+        }
+        Ok(skin)
+    }
+
+    pub fn get(&self, sprite: Sprite) -> &Tile {
+        &self.tiles[match sprite {
+            Sprite::Tetrimino(tetrimino) => match tetrimino {
+                Tetrimino::O => 0,
+                Tetrimino::I => 1,
+                Tetrimino::T => 2,
+                Tetrimino::L => 3,
+                Tetrimino::J => 4,
+                Tetrimino::S => 5,
+                Tetrimino::Z => 6,
+                Tetrimino::Ghost => 7,
+                Tetrimino::Garbage => 10,
+            },
+            Sprite::Wall => 8,
+            Sprite::Empty => 9,
+        }]
+    }
+}
+
+// Procedural skin in guideline colors, indexed like CELLS, with `block` drawing the solid blocks
+fn fallback(idx: usize, block: fn(Rgb888) -> Tile) -> Tile {
+    match idx {
+        0 => block(Rgb888::new(240, 220, 0)),
+        1 => block(Rgb888::new(0, 220, 240)),
+        2 => block(Rgb888::new(160, 0, 240)),
+        3 => block(Rgb888::new(240, 160, 0)),
+        4 => block(Rgb888::new(0, 0, 240)),
+        5 => block(Rgb888::new(0, 220, 0)),
+        6 => block(Rgb888::new(240, 0, 0)),
+        7 => Tile::from_fn(|x, y| {
+            if x == 0 || y == 0 || x == BLOCK - 1 || y == BLOCK - 1 {
+                Rgb888::new(160, 160, 160)
+            } else {
+                Rgb888::BLACK
+            }
+        }),
+        8 => block(Rgb888::new(80, 80, 80)),
+        10 => block(Rgb888::new(140, 140, 140)),
+        _ => Tile::from_fn(|_, _| Rgb888::BLACK),
+    }
+}
+
+// Flat center with a lighter top-left and darker bottom-right edge
+fn bevelled(color: Rgb888) -> Tile {
+    let light = Rgb888::new(
+        color.r().saturating_add(80),
+        color.g().saturating_add(80),
+        color.b().saturating_add(80),
+    );
+    let dark = Rgb888::new(color.r() / 2, color.g() / 2, color.b() / 2);
+    Tile::from_fn(|x, y| {
+        if (x < 2 && y < BLOCK - x) || (y < 2 && x < BLOCK - y) {
+            light
+        } else if x >= BLOCK - 2 || y >= BLOCK - 2 {
+            dark
+        } else {
+            color
+        }
+    })
+}
+
+// One color with a black outline so neighbouring blocks stay apart
+fn flat(color: Rgb888) -> Tile {
+    Tile::from_fn(|x, y| {
+        if x == BLOCK - 1 || y == BLOCK - 1 {
+            Rgb888::BLACK
+        } else {
+            color
+        }
+    })
+}
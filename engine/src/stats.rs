@@ -0,0 +1,90 @@
+use crate::game::Tetrimino;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Indexes `Stats::distribution`
+pub const PIECES: [Tetrimino; 7] = [
+    Tetrimino::O,
+    Tetrimino::I,
+    Tetrimino::T,
+    Tetrimino::L,
+    Tetrimino::J,
+    Tetrimino::S,
+    Tetrimino::Z,
+];
+
+const LINES_PER_LEVEL: u32 = 10;
+
+#[derive(Clone, Default)]
+pub struct Stats {
+    pub time: u64, // ms spent playing, pauses excluded
+    pub lines: u32,
+    pub pieces: u32,
+    pub keys: u32,
+    pub attack: u32, // lines sent under the player's attack table, before countering
+    pub faults: u32, // pieces placed with more key presses than needed
+    pub distribution: [u32; 7],
+}
+
+impl Stats {
+    pub fn level(&self) -> u32 {
+        self.lines / LINES_PER_LEVEL + 1
+    }
+
+    pub fn lock(&mut self, tetrimino: Tetrimino) {
+        self.pieces += 1;
+        if let Some(idx) = PIECES.iter().position(|p| *p == tetrimino) {
+            self.distribution[idx] += 1;
+        }
+    }
+
+    pub fn clear(&mut self, lines: usize) {
+        self.lines += lines as u32;
+    }
+
+    // Rates are in hundredths to stay in integer math
+    pub fn pps(&self) -> u64 {
+        per(self.pieces as u64 * 100_000, self.time)
+    }
+
+    pub fn apm(&self) -> u64 {
+        per(self.attack as u64 * 6_000_000, self.time)
+    }
+
+    pub fn kpp(&self) -> u64 {
+        per(self.keys as u64 * 100, self.pieces as u64)
+    }
+
+    // Label and value of every statistic, in display order
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        let mut rows = vec![
+            ("Time", format_time(self.time)),
+            ("Lines", format!("{}", self.lines)),
+            ("Level", format!("{}", self.level())),
+            ("Pieces", format!("{}", self.pieces)),
+            ("PPS", format_rate(self.pps())),
+            ("APM", format_rate(self.apm())),
+            ("KPP", format_rate(self.kpp())),
+            ("Faults", format!("{}", self.faults)),
+        ];
+        for (idx, name) in ["O", "I", "T", "L", "J", "S", "Z"].iter().enumerate() {
+            rows.push((*name, format!("{}", self.distribution[idx])));
+        }
+        rows
+    }
+}
+
+// m:ss.t
+pub fn format_time(ms: u64) -> String {
+    format!("{}:{:02}.{}", ms / 60_000, ms / 1000 % 60, ms / 100 % 10)
+}
+
+fn format_rate(hundredths: u64) -> String {
+    format!("{}.{:02}", hundredths / 100, hundredths % 100)
+}
+
+fn per(value: u64, divisor: u64) -> u64 {
+    value.checked_div(divisor).unwrap_or(0)
+}
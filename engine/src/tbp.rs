@@ -0,0 +1,718 @@
+use crate::game::{Clear, GameData, GameState, Spin, Tetrimino};
+use crate::replay::{self, Input};
+use crate::settings::Action;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Frontend side of the Tetris Bot Protocol: one JSON message per line,
+// https://github.com/tetris-bot-protocol/tbp-spec
+
+// Rows and columns of the board as bots see it, row 0 at the bottom
+const ROWS: usize = 40;
+const COLUMNS: usize = 10;
+// Where that board starts in the matrix
+const LEFT: usize = 4;
+const FLOOR: usize = 4;
+
+const ORIENTATIONS: [&str; 4] = ["north", "east", "south", "west"];
+
+// A placement as bots describe it, `x` and `y` are the piece's rotation center on the board
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Move {
+    pub piece: Tetrimino,
+    pub rot: u8, // 0 north, clockwise from there like the engine
+    pub x: i32,
+    pub y: i32,
+    pub spin: Spin,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum BotMessage {
+    Info {
+        name: String,
+        version: String,
+        author: String,
+    },
+    Ready,
+    Error(String),
+    Suggestion(Vec<Move>), // best first
+}
+
+pub fn rules() -> String {
+    String::from(r#"{"type":"rules"}"#)
+}
+
+pub fn suggest() -> String {
+    String::from(r#"{"type":"suggest"}"#)
+}
+
+pub fn stop() -> String {
+    String::from(r#"{"type":"stop"}"#)
+}
+
+pub fn quit() -> String {
+    String::from(r#"{"type":"quit"}"#)
+}
+
+pub fn new_piece(piece: Tetrimino) -> String {
+    format!(r#"{{"type":"new_piece","piece":"{}"}}"#, piece_name(piece))
+}
+
+pub fn play(mv: &Move) -> String {
+    format!(r#"{{"type":"play","move":{}}}"#, move_json(mv))
+}
+
+// Parses one line from the bot, unknown message types are an error
+pub fn parse(line: &str) -> Result<BotMessage, String> {
+    let value = Json::parse(line)?;
+    match value.get("type").and_then(Json::as_str) {
+        Some("info") => {
+            let field =
+                |name| String::from(value.get(name).and_then(Json::as_str).unwrap_or_default());
+            Ok(BotMessage::Info {
+                name: field("name"),
+                version: field("version"),
+                author: field("author"),
+            })
+        }
+        Some("ready") => Ok(BotMessage::Ready),
+        Some("error") => Ok(BotMessage::Error(String::from(
+            value
+                .get("reason")
+                .and_then(Json::as_str)
+                .unwrap_or("unknown"),
+        ))),
+        Some("suggestion") => match value.get("moves") {
+            Some(Json::Array(moves)) => moves
+                .iter()
+                .map(parse_move)
+                .collect::<Result<Vec<Move>, String>>()
+                .map(BotMessage::Suggestion),
+            _ => Err(String::from("suggestion without moves")),
+        },
+        Some(kind) => Err(format!("unknown message type `{}`", kind)),
+        None => Err(String::from("message without a type")),
+    }
+}
+
+fn parse_move(value: &Json) -> Result<Move, String> {
+    let location = value.get("location").ok_or("move without a location")?;
+    let piece = location
+        .get("type")
+        .and_then(Json::as_str)
+        .and_then(parse_piece)
+        .ok_or("invalid piece")?;
+    let rot = location
+        .get("orientation")
+        .and_then(Json::as_str)
+        .and_then(|name| ORIENTATIONS.iter().position(|o| *o == name))
+        .ok_or("invalid orientation")? as u8;
+    // Rotation centers are always on the board, checked before the cast so huge numbers can't wrap
+    let coord = |name, max: usize| {
+        location
+            .get(name)
+            .and_then(Json::as_num)
+            .filter(|num| (0.0..max as f64).contains(num))
+            .map(|num| num as i32)
+            .ok_or_else(|| format!("invalid {}", name))
+    };
+    let spin = match value.get("spin").and_then(Json::as_str) {
+        None | Some("none") => Spin::None,
+        Some("mini") => Spin::Mini,
+        Some("full") => Spin::Full,
+        Some(spin) => return Err(format!("invalid spin `{}`", spin)),
+    };
+    Ok(Move {
+        piece,
+        rot,
+        x: coord("x", COLUMNS)?,
+        y: coord("y", ROWS)?,
+        spin,
+    })
+}
+
+fn move_json(mv: &Move) -> String {
+    let spin = match mv.spin {
+        Spin::None => "none",
+        Spin::Mini => "mini",
+        Spin::Full => "full",
+    };
+    format!(
+        r#"{{"location":{{"type":"{}","orientation":"{}","x":{},"y":{}}},"spin":"{}"}}"#,
+        piece_name(mv.piece),
+        ORIENTATIONS[mv.rot as usize % 4],
+        mv.x,
+        mv.y,
+        spin
+    )
+}
+
+pub(crate) fn piece_name(piece: Tetrimino) -> &'static str {
+    match piece {
+        Tetrimino::O => "O",
+        Tetrimino::I => "I",
+        Tetrimino::T => "T",
+        Tetrimino::L => "L",
+        Tetrimino::J => "J",
+        Tetrimino::S => "S",
+        Tetrimino::Z => "Z",
+        Tetrimino::Ghost | Tetrimino::Garbage => "G",
+    }
+}
+
+pub(crate) fn parse_piece(name: &str) -> Option<Tetrimino> {
+    match name {
+        "O" => Some(Tetrimino::O),
+        "I" => Some(Tetrimino::I),
+        "T" => Some(Tetrimino::T),
+        "L" => Some(Tetrimino::L),
+        "J" => Some(Tetrimino::J),
+        "S" => Some(Tetrimino::S),
+        "Z" => Some(Tetrimino::Z),
+        _ => None,
+    }
+}
+
+// Cells of the piece in north orientation relative to its center, turned clockwise for the others
+fn north_cells(piece: Tetrimino) -> [(i32, i32); 4] {
+    match piece {
+        Tetrimino::O => [(0, 0), (1, 0), (0, 1), (1, 1)],
+        Tetrimino::I => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+        Tetrimino::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+        Tetrimino::L => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+        Tetrimino::J => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+        Tetrimino::S => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+        _ => [(-1, 1), (0, 1), (0, 0), (1, 0)],
+    }
+}
+
+// Cells of the piece relative to its center in any orientation, fumen uses the same convention
+pub(crate) fn center_cells(piece: Tetrimino, rot: u8) -> [(i32, i32); 4] {
+    let mut cells = north_cells(piece);
+    for cell in cells.iter_mut() {
+        let (x, y) = *cell;
+        *cell = match rot % 4 {
+            0 => (x, y),
+            1 => (y, -x),
+            2 => (-x, -y),
+            _ => (-y, x),
+        };
+    }
+    cells
+}
+
+// Matrix cells of a move, `None` if any of them is off the matrix
+fn move_cells(mv: &Move) -> Option<Vec<(usize, usize)>> {
+    center_cells(mv.piece, mv.rot)
+        .iter()
+        .map(|&(x, y)| {
+            let (x, y) = (mv.x + x + LEFT as i32, mv.y + y + FLOOR as i32);
+            if x < 0 || y < 0 {
+                None
+            } else {
+                Some((x as usize, y as usize))
+            }
+        })
+        .collect()
+}
+
+// The engine position (top left of the SRS box) that covers the cells of a move
+pub(crate) fn position(mv: &Move) -> Option<(usize, usize)> {
+    let mut cells = move_cells(mv)?;
+    cells.sort_unstable();
+    let origin = (8, 30);
+    let mut reference = mv.piece.cells(origin, mv.rot);
+    reference.sort_unstable();
+    let pos = (
+        (origin.0 + cells[0].0).checked_sub(reference[0].0)?,
+        (origin.1 + cells[0].1).checked_sub(reference[0].1)?,
+    );
+    let mut placed = mv.piece.cells(pos, mv.rot);
+    placed.sort_unstable();
+    if placed == cells {
+        Some(pos)
+    } else {
+        None
+    }
+}
+
+// The rotation center of a piece at an engine position, the other way around
+pub(crate) fn center(piece: Tetrimino, pos: (usize, usize), rot: u8) -> (i32, i32) {
+    let mut cells = piece.cells(pos, rot);
+    cells.sort_unstable();
+    let mut offsets = center_cells(piece, rot);
+    offsets.sort_unstable();
+    (
+        cells[0].0 as i32 - LEFT as i32 - offsets[0].0,
+        cells[0].1 as i32 - FLOOR as i32 - offsets[0].1,
+    )
+}
+
+// The board and queue as a `start` message, the queue starts with the active piece
+pub fn start(data: &GameData) -> String {
+    let rows: Vec<String> = (FLOOR..FLOOR + ROWS)
+        .map(|y| {
+            let cells: Vec<String> = (LEFT..LEFT + COLUMNS)
+                .map(|x| match data.matrix[y][x] {
+                    Some(piece) => format!(r#""{}""#, piece_name(piece)),
+                    None => String::from("null"),
+                })
+                .collect();
+            format!("[{}]", cells.join(","))
+        })
+        .collect();
+    let queue: Vec<String> = core::iter::once(data.active_mino.0)
+        .chain(data.queue.iter().copied())
+        .map(|piece| format!(r#""{}""#, piece_name(piece)))
+        .collect();
+    let hold = match data.hold {
+        Some(piece) => format!(r#""{}""#, piece_name(piece)),
+        None => String::from("null"),
+    };
+    format!(
+        r#"{{"type":"start","hold":{},"queue":[{}],"combo":{},"back_to_back":{},"board":[{}]}}"#,
+        hold,
+        queue.join(","),
+        (data.combo + 1).max(0),
+        data.back_to_back,
+        rows.join(",")
+    )
+}
+
+// Keeps a bot's view of the queue in step with the game
+#[derive(Default)]
+pub struct Feed {
+    known: usize, // pieces of the engine queue the bot was told about
+}
+
+impl Feed {
+    pub fn start(&mut self, data: &GameData) -> String {
+        self.known = data.queue.len();
+        start(data)
+    }
+
+    // Places and locks the piece where the bot wants it, holding first if the move needs it.
+    // Returns the clear and the messages that tell the bot about it
+    pub fn play(
+        &mut self,
+        data: &mut GameData,
+        mv: &Move,
+    ) -> Result<(Option<Clear>, Vec<String>), String> {
+        if data.state != GameState::Drop {
+            return Err(String::from("the game is not running"));
+        }
+        // Everything is checked against the piece that gets played before the hold changes the game
+        let hold = mv.piece != data.active_mino.0;
+        if hold {
+            let held = data
+                .hold
+                .or_else(|| data.queue.front().copied())
+                .ok_or("there is no piece to hold")?;
+            if data.hold_flag || held != mv.piece {
+                return Err(format!(
+                    "{} is neither the active nor the held piece",
+                    piece_name(mv.piece)
+                ));
+            }
+        }
+        let (x, y) = position(mv).ok_or("move is outside the board")?;
+        if mv.piece.collides(&data.matrix, (x, y), mv.rot) {
+            return Err(String::from("move overlaps the stack"));
+        }
+        if !mv.piece.collides(&data.matrix, (x, y - 1), mv.rot) {
+            return Err(String::from("move is floating"));
+        }
+
+        let mut consumed = 0;
+        if hold {
+            if data.hold.is_none() {
+                consumed += 1;
+            }
+            replay::apply(data, Input::Action(Action::Hold));
+        }
+        data.active_mino = (mv.piece, x, y, mv.rot);
+        // How the piece got there is up to the bot, the engine only checks the corners
+        data.rotated = mv.spin != Spin::None;
+        let clear = replay::apply(data, Input::Lock);
+        consumed += 1;
+
+        let mut messages = vec![play(mv)];
+        self.known = self.known.saturating_sub(consumed);
+        messages.extend(
+            data.queue
+                .iter()
+                .skip(self.known)
+                .map(|piece| new_piece(*piece)),
+        );
+        self.known = data.queue.len();
+        Ok((clear, messages))
+    }
+}
+
+// Just enough JSON for the protocol
+#[derive(Clone, PartialEq, Debug)]
+enum Json {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn parse(src: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            src: src.as_bytes(),
+            pos: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_space();
+        if parser.pos != parser.src.len() {
+            return Err(format!("trailing characters at {}", parser.pos));
+        }
+        Ok(value)
+    }
+
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_num(&self) -> Option<f64> {
+        match self {
+            Json::Num(num) => Some(*num),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    src: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_space(&mut self) {
+        while self.pos < self.src.len() && self.src[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_space();
+        self.src.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected `{}` at {}", byte as char, self.pos))
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        if self.src[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            Ok(value)
+        } else {
+            Err(format!("invalid literal at {}", self.pos))
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    fields.push((key, self.value()?));
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b'}')?;
+                Ok(Json::Object(fields))
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b']')?;
+                Ok(Json::Array(items))
+            }
+            Some(b'"') => self.string().map(Json::Str),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(_) => {
+                let start = self.pos;
+                while self.pos < self.src.len()
+                    && matches!(
+                        self.src[self.pos],
+                        b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'
+                    )
+                {
+                    self.pos += 1;
+                }
+                core::str::from_utf8(&self.src[start..self.pos])
+                    .ok()
+                    .and_then(|num| num.parse().ok())
+                    .map(Json::Num)
+                    .ok_or_else(|| format!("invalid value at {}", start))
+            }
+            None => Err(String::from("unexpected end of input")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+        loop {
+            let byte = *self.src.get(self.pos).ok_or("unterminated string")?;
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let escaped = *self.src.get(self.pos).ok_or("unterminated string")?;
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => bytes.push(b'\n'),
+                        b't' => bytes.push(b'\t'),
+                        b'r' => bytes.push(b'\r'),
+                        b'b' => bytes.push(8),
+                        b'f' => bytes.push(12),
+                        b'u' => {
+                            let hex = self
+                                .src
+                                .get(self.pos..self.pos + 4)
+                                .ok_or("invalid escape")?;
+                            self.pos += 4;
+                            let code = core::str::from_utf8(hex)
+                                .ok()
+                                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                                .ok_or("invalid escape")?;
+                            // Surrogate pairs never show up in the protocol, they become U+FFFD
+                            let c = core::char::from_u32(code).unwrap_or('\u{fffd}');
+                            let mut buffer = [0; 4];
+                            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                        }
+                        other => bytes.push(other),
+                    }
+                }
+                _ => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| String::from("invalid UTF-8 in string"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::PIECES;
+
+    #[test]
+    fn bot_messages() {
+        let info = r#"{"type":"info","name":"Cold \"Clear\"","version":"2é","author":"x\ny","features":[]}"#;
+        assert_eq!(
+            parse(info),
+            Ok(BotMessage::Info {
+                name: String::from("Cold \"Clear\""),
+                version: String::from("2é"),
+                author: String::from("x\ny"),
+            })
+        );
+        assert_eq!(parse(r#" { "type" : "ready" } "#), Ok(BotMessage::Ready));
+        assert_eq!(
+            parse(r#"{"type":"error","reason":"unsupported_rules"}"#),
+            Ok(BotMessage::Error(String::from("unsupported_rules")))
+        );
+
+        let moves = [
+            Move {
+                piece: Tetrimino::T,
+                rot: 2,
+                x: 4,
+                y: 1,
+                spin: Spin::Full,
+            },
+            Move {
+                piece: Tetrimino::I,
+                rot: 1,
+                x: 0,
+                y: 39,
+                spin: Spin::None,
+            },
+        ];
+        let line = format!(
+            r#"{{"type":"suggestion","moves":[{},{}],"move_info":{{"nodes":1.5e3}}}}"#,
+            move_json(&moves[0]),
+            move_json(&moves[1])
+        );
+        assert_eq!(parse(&line), Ok(BotMessage::Suggestion(moves.to_vec())));
+    }
+
+    #[test]
+    fn malformed_json() {
+        let inputs = [
+            "",
+            "   ",
+            "{",
+            r#"{"type":"ready""#,
+            r#"{"type":"ready",}"#,
+            r#"{"type" "ready"}"#,
+            r#"{"type":"ready"} x"#,
+            r#"{type:"ready"}"#,
+            r#"{"type":"rea"#,
+            r#"{"type":"\u12"}"#,
+            r#"{"type":tru}"#,
+            r#"{"type":-}"#,
+            "[1,]",
+            "\"\\",
+        ];
+        for input in inputs.iter() {
+            assert!(parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn unknown_and_invalid_messages() {
+        let error = |line: &str| parse(line).err().unwrap();
+        assert_eq!(error(r#"{"type":"hello"}"#), "unknown message type `hello`");
+        assert_eq!(error("{}"), "message without a type");
+        assert_eq!(error(r#"{"type":3}"#), "message without a type");
+        assert_eq!(error("[]"), "message without a type");
+        assert_eq!(
+            error(r#"{"type":"suggestion"}"#),
+            "suggestion without moves"
+        );
+
+        let suggestion = |mv: &str| error(&format!(r#"{{"type":"suggestion","moves":[{}]}}"#, mv));
+        let location = |fields: &str| suggestion(&format!(r#"{{"location":{{{}}}}}"#, fields));
+        assert_eq!(suggestion("{}"), "move without a location");
+        let t = r#""type":"T","orientation":"north""#;
+        assert_eq!(location(r#""type":"X""#), "invalid piece");
+        assert_eq!(
+            location(r#""type":"T","orientation":"up""#),
+            "invalid orientation"
+        );
+        assert_eq!(location(&format!(r#"{},"y":0"#, t)), "invalid x");
+        assert_eq!(location(&format!(r#"{},"x":10,"y":0"#, t)), "invalid x");
+        assert_eq!(location(&format!(r#"{},"x":-1,"y":0"#, t)), "invalid x");
+        assert_eq!(location(&format!(r#"{},"x":4,"y":40"#, t)), "invalid y");
+        assert_eq!(location(&format!(r#"{},"x":4,"y":1e30"#, t)), "invalid y");
+        assert_eq!(
+            suggestion(&format!(
+                r#"{{"location":{{{},"x":4,"y":0}},"spin":"half"}}"#,
+                t
+            )),
+            "invalid spin `half`"
+        );
+    }
+
+    #[test]
+    fn centers_map_to_engine_positions() {
+        for piece in PIECES.iter() {
+            for rot in 0..4 {
+                for x in 0..LEFT + COLUMNS {
+                    for y in 3..FLOOR + ROWS {
+                        let mut cells = piece.cells((x, y), rot);
+                        let on_board = cells.iter().all(|&(x, y)| {
+                            (LEFT..LEFT + COLUMNS).contains(&x)
+                                && (FLOOR..FLOOR + ROWS).contains(&y)
+                        });
+                        if !on_board {
+                            continue;
+                        }
+                        let (cx, cy) = center(*piece, (x, y), rot);
+                        assert!((0..COLUMNS as i32).contains(&cx));
+                        assert!((0..ROWS as i32).contains(&cy));
+                        let mv = Move {
+                            piece: *piece,
+                            rot,
+                            x: cx,
+                            y: cy,
+                            spin: Spin::None,
+                        };
+                        assert_eq!(position(&mv), Some((x, y)), "{:?} {}", piece, rot);
+                        let mut moved = move_cells(&mv).unwrap();
+                        moved.sort_unstable();
+                        cells.sort_unstable();
+                        assert_eq!(moved, cells);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn centers_follow_the_protocol() {
+        // Board cells as bots count them, from the bottom left
+        let cells = |piece, rot, x, y| {
+            let mut cells: Vec<(i32, i32)> = move_cells(&Move {
+                piece,
+                rot,
+                x,
+                y,
+                spin: Spin::None,
+            })
+            .unwrap()
+            .iter()
+            .map(|&(x, y)| (x as i32 - LEFT as i32, y as i32 - FLOOR as i32))
+            .collect();
+            cells.sort_unstable();
+            cells
+        };
+        assert_eq!(
+            cells(Tetrimino::T, 0, 4, 0),
+            [(3, 0), (4, 0), (4, 1), (5, 0)]
+        );
+        assert_eq!(
+            cells(Tetrimino::T, 2, 4, 1),
+            [(3, 1), (4, 0), (4, 1), (5, 1)]
+        );
+        assert_eq!(
+            cells(Tetrimino::O, 0, 0, 0),
+            [(0, 0), (0, 1), (1, 0), (1, 1)]
+        );
+        assert_eq!(
+            cells(Tetrimino::I, 0, 1, 0),
+            [(0, 0), (1, 0), (2, 0), (3, 0)]
+        );
+        assert_eq!(
+            cells(Tetrimino::I, 1, 0, 2),
+            [(0, 0), (0, 1), (0, 2), (0, 3)]
+        );
+    }
+}
@@ -0,0 +1,434 @@
+use crate::effects::{Effect, Effects};
+use crate::frontend::Frontend;
+use crate::game::{GameData, GameState, Tetrimino, VISIBLE_ROWS};
+use crate::layout::Hand;
+use crate::settings::Settings;
+use crate::stats::{self, Stats};
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// The 16 colours every text console has
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Color {
+    Black,
+    Blue,
+    Green,
+    Cyan,
+    Red,
+    Magenta,
+    Brown,
+    LightGray,
+    DarkGray,
+    LightBlue,
+    LightGreen,
+    LightCyan,
+    LightRed,
+    LightMagenta,
+    Yellow,
+    White,
+}
+
+// A grid of characters, like the firmware text console or a terminal
+pub trait Terminal {
+    // Columns and rows
+    fn size(&self) -> (usize, usize);
+    fn clear(&mut self);
+    fn write(&mut self, x: usize, y: usize, text: &str, fg: Color, bg: Color);
+    fn flush(&mut self) {}
+}
+
+// Sizes in characters, every block is two columns wide to look roughly square
+const SIDE_WIDTH: usize = 8;
+const BOARD_WIDTH: usize = 2 * 10 + 2; // with borders
+const CLUSTER_WIDTH: usize = SIDE_WIDTH + 1 + BOARD_WIDTH + 1 + SIDE_WIDTH;
+// Board with borders and the timer below it
+const HEIGHT: usize = 20 + 2 + 1;
+const HOLD_HEIGHT: usize = 4;
+const MENU_LINES: usize = 16;
+const MENU_COLUMNS: usize = 48;
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+struct Cell {
+    c: char,
+    fg: Color,
+    bg: Color,
+}
+
+const BLANK: Cell = Cell {
+    c: ' ',
+    fg: Color::LightGray,
+    bg: Color::Black,
+};
+
+// Top left corners of the panels of a single player
+#[derive(Copy, Clone, Eq, PartialEq)]
+struct BoardLayout {
+    board: (usize, usize), // top left border corner
+    hold: (usize, usize),
+    queue: (usize, usize),
+    stats: (usize, usize),
+    timer: (usize, usize),
+}
+
+pub struct TextUi<T: Terminal> {
+    term: T,
+    size: (usize, usize),
+    previews: usize,
+    boards: Vec<BoardLayout>,
+    // What is drawn and what the terminal shows, only differences get written out
+    cells: Vec<Cell>,
+    shown: Vec<Cell>,
+}
+
+impl<T: Terminal> TextUi<T> {
+    pub fn new(term: T, settings: &Settings, players: u32) -> Self {
+        let size = term.size();
+        let mut ui = Self {
+            term,
+            size,
+            previews: 0,
+            boards: Vec::new(),
+            cells: vec![BLANK; size.0 * size.1],
+            shown: Vec::new(),
+        };
+        ui.set_layout(settings, players);
+        ui.draw_background();
+        ui
+    }
+
+    pub fn terminal(&mut self) -> &mut T {
+        &mut self.term
+    }
+
+    // Anything outside of the terminal is cut off
+    fn put(&mut self, x: usize, y: usize, text: &str, fg: Color) {
+        if y >= self.size.1 {
+            return;
+        }
+        for (idx, c) in text.chars().enumerate() {
+            if x + idx >= self.size.0 {
+                break;
+            }
+            self.cells[y * self.size.0 + x + idx] = Cell {
+                c,
+                fg,
+                bg: Color::Black,
+            };
+        }
+    }
+
+    fn clear_rect(&mut self, (x, y): (usize, usize), width: usize, height: usize) {
+        let blank = " ".repeat(width);
+        for row in y..y + height {
+            self.put(x, row, &blank, Color::LightGray);
+        }
+    }
+
+    // Screen position of a matrix cell, `None` above the top border
+    fn cell(&self, board: usize, (x, y): (usize, usize)) -> Option<(usize, usize)> {
+        if !(4..14).contains(&x) || y > VISIBLE_ROWS.end {
+            return None;
+        }
+        let (left, top) = self.boards[board].board;
+        Some((left + 1 + (x - 4) * 2, top + VISIBLE_ROWS.end - y))
+    }
+
+    fn draw_piece(&mut self, tetrimino: Tetrimino, (x, y): (usize, usize), color: Color) {
+        // Spawn orientations fit into the top two rows of the 4x4 box
+        for (col, row) in tetrimino.cells((0, 3), 0) {
+            self.put(x + col * 2, y + 3 - row, "██", color);
+        }
+    }
+}
+
+fn color(tetrimino: Tetrimino) -> Color {
+    match tetrimino {
+        Tetrimino::O => Color::Yellow,
+        Tetrimino::I => Color::LightCyan,
+        Tetrimino::T => Color::LightMagenta,
+        Tetrimino::L => Color::Brown,
+        Tetrimino::J => Color::LightBlue,
+        Tetrimino::S => Color::LightGreen,
+        Tetrimino::Z => Color::LightRed,
+        Tetrimino::Ghost => Color::DarkGray,
+        Tetrimino::Garbage => Color::LightGray,
+    }
+}
+
+impl<T: Terminal> Frontend for TextUi<T> {
+    fn set_layout(&mut self, settings: &Settings, players: u32) -> bool {
+        let size = self.term.size();
+        let players = players as usize;
+        // Centered like the graphical layout, with the top row left for messages
+        let origin = (
+            size.0.saturating_sub(CLUSTER_WIDTH * players) / 2,
+            size.1.saturating_sub(HEIGHT + 1) / 2 + 1,
+        );
+        let boards: Vec<BoardLayout> = (0..players)
+            .map(|idx| {
+                let left = origin.0 + CLUSTER_WIDTH * idx;
+                let board_x = left + SIDE_WIDTH + 1;
+                let right = board_x + BOARD_WIDTH + 1;
+                let (hold_x, queue_x) = match settings.hand {
+                    Hand::Right => (left, right),
+                    Hand::Left => (right, left),
+                };
+                BoardLayout {
+                    board: (board_x, origin.1),
+                    hold: (hold_x, origin.1),
+                    queue: (queue_x, origin.1),
+                    stats: (hold_x, origin.1 + HOLD_HEIGHT + 1),
+                    timer: (board_x, origin.1 + HEIGHT - 1),
+                }
+            })
+            .collect();
+
+        let changed =
+            size != self.size || boards != self.boards || settings.previews != self.previews;
+        if size != self.size {
+            self.size = size;
+            self.cells = vec![BLANK; size.0 * size.1];
+            self.shown.clear();
+        }
+        self.boards = boards;
+        self.previews = settings.previews;
+        changed
+    }
+
+    fn draw_background(&mut self) {
+        for cell in self.cells.iter_mut() {
+            *cell = BLANK;
+        }
+        for (x, y) in self.boards.clone().iter().map(|b| b.board) {
+            self.put(x, y, &format!("┌{}┐", "─".repeat(20)), Color::LightGray);
+            for row in y + 1..y + 21 {
+                self.put(x, row, "│", Color::LightGray);
+                self.put(x + 21, row, "│", Color::LightGray);
+            }
+            self.put(
+                x,
+                y + 21,
+                &format!("└{}┘", "─".repeat(20)),
+                Color::LightGray,
+            );
+        }
+        for (x, y) in self.boards.clone().iter().map(|b| b.hold) {
+            self.put(x, y, "HOLD", Color::White);
+        }
+        if self.previews > 0 {
+            for (x, y) in self.boards.clone().iter().map(|b| b.queue) {
+                self.put(x, y, "NEXT", Color::White);
+            }
+        }
+    }
+
+    // The top border stands in for the buffer zone and turns red while the stack is close to it
+    fn draw_board(&mut self, board: usize, data: &GameData) {
+        let (left, top) = self.boards[board].board;
+        let border = if data.danger() {
+            Color::LightRed
+        } else {
+            Color::LightGray
+        };
+        self.put(left, top, &format!("┌{}┐", "─".repeat(20)), border);
+
+        for y in VISIBLE_ROWS {
+            for x in 4..14 {
+                let pos = self.cell(board, (x, y)).unwrap();
+                match data.matrix[y][x] {
+                    Some(mino) => self.put(pos.0, pos.1, "██", color(mino)),
+                    None => self.put(pos.0, pos.1, " .", Color::DarkGray),
+                }
+            }
+        }
+
+        if let GameState::Drop | GameState::Settings = data.state {
+            let (tetrimino, x, y, rot) = data.active_mino;
+            for cell in tetrimino.cells(data.ghost(), rot) {
+                if let Some(pos) = self.cell(board, cell) {
+                    self.put(pos.0, pos.1, "░░", color(tetrimino));
+                }
+            }
+            for cell in tetrimino.cells((x, y), rot) {
+                if let Some(pos) = self.cell(board, cell) {
+                    self.put(pos.0, pos.1, "██", color(tetrimino));
+                }
+            }
+        }
+    }
+
+    fn draw_queue(&mut self, board: usize, queue: &VecDeque<Tetrimino>) {
+        let count = self.previews;
+        if count == 0 {
+            return;
+        }
+        let (x, y) = self.boards[board].queue;
+        self.clear_rect((x, y + 1), SIDE_WIDTH, 20);
+        // Three rows per piece, two when all seven have to fit next to the board
+        let spacing = 3.min(20 / count);
+        for (i, next) in queue.iter().take(count).enumerate() {
+            self.draw_piece(*next, (x, y + 1 + i * spacing), color(*next));
+        }
+    }
+
+    fn draw_hold(&mut self, board: usize, content: &Option<Tetrimino>, locked: bool) {
+        let (x, y) = self.boards[board].hold;
+        self.clear_rect((x, y + 1), SIDE_WIDTH, HOLD_HEIGHT - 1);
+        if let Some(tetrimino) = content {
+            let color = if locked {
+                Color::DarkGray
+            } else {
+                color(*tetrimino)
+            };
+            self.draw_piece(*tetrimino, (x, y + 1), color);
+        }
+    }
+
+    fn draw_stats(&mut self, board: usize, stats: &Stats) {
+        let pos = self.boards[board].stats;
+        self.clear_rect(pos, SIDE_WIDTH, 15);
+        // Time has its own line below the board
+        for (idx, (name, value)) in stats.rows().iter().skip(1).enumerate() {
+            let line = if idx < 7 { idx } else { idx + 1 };
+            let mut text = format!("{:<3.3}{:>5}", name, value);
+            text.truncate(SIDE_WIDTH);
+            self.put(pos.0, pos.1 + line, &text, Color::LightGray);
+        }
+        self.draw_timer(board, stats.time);
+    }
+
+    // A bar in the gap left of the board, one row per line
+    fn draw_garbage(&mut self, board: usize, lines: u32) {
+        let (left, top) = self.boards[board].board;
+        for row in 0..20 {
+            let text = if 20 - row <= lines as usize { "█" } else { " " };
+            self.put(left - 1, top + 1 + row, text, Color::LightRed);
+        }
+    }
+
+    fn draw_timer(&mut self, board: usize, time: u64) {
+        let (x, y) = self.boards[board].timer;
+        let text = stats::format_time(time);
+        self.clear_rect((x, y), BOARD_WIDTH, 1);
+        self.put(x + (BOARD_WIDTH - text.len()) / 2, y, &text, Color::White);
+    }
+
+    // Only clears and popups, the rest is too fine-grained for a character grid
+    fn draw_effects(&mut self, board: usize, effects: &Effects, now: u64) {
+        for active in effects.iter() {
+            let progress = active.progress(now);
+            match &active.effect {
+                Effect::ClearRows(rows) => {
+                    let flash = progress < 128 && ((now - active.start) / 50).is_multiple_of(2);
+                    let gone = if progress < 128 {
+                        0
+                    } else {
+                        (progress - 128) * 6 / 128
+                    };
+                    for row in rows.iter() {
+                        for x in 4..14 {
+                            let distance = (if x < 9 { 9 - x } else { x - 8 }) as u32;
+                            let (text, color) = if distance <= gone {
+                                ("  ", Color::Black)
+                            } else if flash || progress >= 128 {
+                                ("██", Color::White)
+                            } else {
+                                continue;
+                            };
+                            if let Some(pos) = self.cell(board, (x, *row)) {
+                                self.put(pos.0, pos.1, text, color);
+                            }
+                        }
+                    }
+                }
+                Effect::LockFlash(cells) => {
+                    for cell in cells.iter() {
+                        if let Some(pos) = self.cell(board, *cell) {
+                            self.put(pos.0, pos.1, "██", Color::White);
+                        }
+                    }
+                }
+                Effect::Trail { .. } => {}
+                Effect::Popup(lines) => {
+                    let (left, top) = self.boards[board].board;
+                    for (idx, line) in lines.iter().enumerate() {
+                        let x = left + BOARD_WIDTH.saturating_sub(line.len()) / 2;
+                        self.put(x, top + 6 + idx, line, Color::Yellow);
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw_messages(&mut self, title: &str, lines: &[String]) {
+        let width = self.size.0.saturating_sub(2);
+        let top = self.boards.first().map_or(1, |b| b.board.1);
+        self.clear_rect((1, top), width, lines.len() + 3);
+        self.put(2, top + 1, title, Color::Yellow);
+        for (idx, line) in lines.iter().enumerate() {
+            self.put(2, top + 2 + idx, line, Color::White);
+        }
+    }
+
+    fn draw_menu(&mut self, title: &str, items: &[(String, String)], selected: usize, hint: &str) {
+        let first = selected
+            .saturating_sub(MENU_LINES / 2)
+            .min(items.len().saturating_sub(MENU_LINES));
+        let lines: Vec<String> = items
+            .iter()
+            .skip(first)
+            .take(MENU_LINES)
+            .map(|(name, value)| format!("{:<20}{}", name, value))
+            .collect();
+
+        let left = self.size.0.saturating_sub(MENU_COLUMNS) / 2;
+        let top = self.boards.first().map_or(1, |b| b.board.1);
+        self.clear_rect((left, top), MENU_COLUMNS, lines.len() + 5);
+        self.put(left + 1, top + 1, title, Color::White);
+        for (idx, line) in lines.iter().enumerate() {
+            let color = if first + idx == selected {
+                Color::Yellow
+            } else {
+                Color::White
+            };
+            self.put(left + 1, top + 3 + idx, line, color);
+        }
+        self.put(left + 1, top + 4 + lines.len(), hint, Color::LightCyan);
+    }
+
+    // Writes out runs of changed cells that share colours
+    fn refresh(&mut self) {
+        if self.shown.len() != self.cells.len() {
+            self.term.clear();
+            self.shown = vec![BLANK; self.cells.len()];
+        }
+        let width = self.size.0;
+        for y in 0..self.size.1 {
+            let mut x = 0;
+            while x < width {
+                let idx = y * width + x;
+                if self.cells[idx] == self.shown[idx] {
+                    x += 1;
+                    continue;
+                }
+                let Cell { fg, bg, .. } = self.cells[idx];
+                let mut text = String::new();
+                let start = x;
+                while x < width {
+                    let cell = self.cells[y * width + x];
+                    if cell == self.shown[y * width + x] || cell.fg != fg || cell.bg != bg {
+                        break;
+                    }
+                    text.push(cell.c);
+                    x += 1;
+                }
+                self.term.write(start, y, &text, fg, bg);
+            }
+        }
+        self.shown.copy_from_slice(&self.cells);
+        self.term.flush();
+    }
+}
@@ -0,0 +1,538 @@
+use crate::effects::{Effect, Effects};
+use crate::frontend::{Frontend, Video};
+use crate::game::{
+    get_rotation, GameData, GameState, Matrix, Tetrimino, BUFFER_ROWS, VISIBLE_ROWS,
+};
+use crate::layout::{Layout, Rect};
+use crate::settings::Settings;
+use crate::sprites::{Skin, Sprite};
+use crate::stats::{self, Stats};
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use embedded_graphics::drawable::Drawable;
+use embedded_graphics::{
+    egrectangle, egtext,
+    fonts::{Font24x32, Font6x8, Font8x16},
+    pixelcolor::Rgb888,
+    prelude::*,
+    primitive_style, text_style,
+};
+
+// Smallest screen the game is designed for
+pub const WIDTH: u32 = 640;
+pub const HEIGHT: u32 = 480;
+const MENU_LINES: usize = 20;
+// Width of the menu box in characters
+const MENU_COLUMNS: u32 = 48;
+
+// Background of the buffer zone while the stack is close to the top
+const DANGER: Rgb888 = Rgb888::new(96, 0, 0);
+
+// A draw target that can show what has been drawn so far
+pub trait Screen: DrawTarget<Rgb888> {
+    fn present(&mut self);
+
+    fn video(&mut self) -> Option<&mut dyn Video> {
+        None
+    }
+}
+
+// Drops everything drawn outside of `area`
+struct Clipped<'a, D> {
+    target: &'a mut D,
+    area: Rect,
+}
+
+impl<D: DrawTarget<Rgb888>> DrawTarget<Rgb888> for Clipped<'_, D> {
+    type Error = D::Error;
+
+    fn draw_pixel(&mut self, item: Pixel<Rgb888>) -> Result<(), Self::Error> {
+        let Pixel(coord, _) = item;
+        let (top_left, bottom_right) = (self.area.top_left, self.area.bottom_right());
+        if (top_left.x..=bottom_right.x).contains(&coord.x)
+            && (top_left.y..=bottom_right.y).contains(&coord.y)
+        {
+            self.target.draw_pixel(item)?;
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        self.target.size()
+    }
+}
+
+// Draws everything in dark grey
+struct Greyed<'a, D>(&'a mut D);
+
+impl<D: DrawTarget<Rgb888>> DrawTarget<Rgb888> for Greyed<'_, D> {
+    type Error = D::Error;
+
+    fn draw_pixel(&mut self, item: Pixel<Rgb888>) -> Result<(), Self::Error> {
+        let Pixel(coord, color) = item;
+        let level = ((color.r() as u32 + color.g() as u32 + color.b() as u32) / 6) as u8;
+        self.0
+            .draw_pixel(Pixel(coord, Rgb888::new(level, level, level)))
+    }
+
+    fn size(&self) -> Size {
+        self.0.size()
+    }
+}
+
+pub struct Ui<S: Screen> {
+    _score: u32,
+    buffer: S,
+    skin: Skin,
+    layout: Layout,
+}
+
+impl<S: Screen> Ui<S>
+where
+    S::Error: Debug,
+{
+    pub fn new(buffer: S, settings: &Settings, players: u32) -> Self {
+        let layout = Layout::new(buffer.size(), settings, players);
+        let mut ui = Self {
+            _score: 0,
+            buffer,
+            skin: Skin::embedded(),
+            layout,
+        };
+
+        let size = ui.buffer.size();
+        let t = egtext!(
+            text = "Welcome to Rustris!",
+            top_left = (size.width as i32 / 2 - 228, size.height as i32 / 2 - 16),
+            style = text_style!(font = Font24x32, text_color = Rgb888::WHITE)
+        );
+        t.draw(&mut ui.buffer).unwrap();
+        ui.buffer.present();
+        //sleep(Duration::from_secs(2));
+        ui.draw_background();
+        ui
+    }
+
+    pub fn screen(&mut self) -> &mut S {
+        &mut self.buffer
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    fn clear_panel(&mut self, rect: Rect) {
+        self.fill_rect(rect, Rgb888::BLACK);
+    }
+
+    fn fill_rect(&mut self, rect: Rect, color: Rgb888) {
+        let r = egrectangle!(
+            top_left = rect.top_left,
+            bottom_right = rect.bottom_right(),
+            style = primitive_style!(fill_color = color)
+        );
+        r.draw(&mut self.buffer).unwrap();
+    }
+
+    fn draw_text(&mut self, text: &str, pos: Point, color: Rgb888) {
+        let t = egtext!(
+            text = text,
+            top_left = pos,
+            style = text_style!(font = Font8x16, text_color = color)
+        );
+        t.draw(&mut self.buffer).unwrap();
+    }
+
+    // Side panel text falls back to the 6x8 font on blocks too small for 8x16
+    fn panel_font(&self) -> Size {
+        if self.layout.block < 16 {
+            Size::new(6, 8)
+        } else {
+            Size::new(8, 16)
+        }
+    }
+
+    fn draw_panel_text(&mut self, text: &str, pos: Point, color: Rgb888) {
+        if self.layout.block >= 16 {
+            self.draw_text(text, pos, color);
+        } else {
+            let t = egtext!(
+                text = text,
+                top_left = pos,
+                style = text_style!(font = Font6x8, text_color = color)
+            );
+            t.draw(&mut self.buffer).unwrap();
+        }
+    }
+
+    pub fn draw_matrix(&mut self, board: usize, matrix: &Matrix) {
+        let origin = self.layout.boards[board].board.top_left;
+        let block = self.layout.block as i32;
+        let rows = matrix[VISIBLE_ROWS].iter().rev();
+        for (row, minos) in rows.enumerate() {
+            for (col, mino) in minos.iter().skip(4).take(10).enumerate() {
+                let sprite = if let Some(mino) = mino {
+                    self.skin.get(Sprite::Tetrimino(*mino))
+                } else {
+                    self.skin.get(Sprite::Empty)
+                };
+                let pos = origin + Point::new(col as i32, row as i32) * block;
+                sprite.draw(&mut self.buffer, pos, block as u32).unwrap();
+            }
+        }
+    }
+
+    // Screen position of a matrix cell, if it is in the visible matrix or buffer zone
+    fn cell(&self, board: usize, (x, y): (usize, usize)) -> Option<Point> {
+        if !(4..14).contains(&x) || !(VISIBLE_ROWS.start..BUFFER_ROWS.end).contains(&y) {
+            return None;
+        }
+        let block = self.layout.block as i32;
+        Some(
+            self.layout.boards[board].board.top_left
+                + Point::new(x as i32 - 4, 23 - y as i32) * block,
+        )
+    }
+
+    // Effects are only drawn on the visible matrix
+    fn fill_cell(&mut self, board: usize, cell: (usize, usize), color: Rgb888) {
+        if !VISIBLE_ROWS.contains(&cell.1) {
+            return;
+        }
+        if let Some(pos) = self.cell(board, cell) {
+            let block = self.layout.block;
+            self.fill_rect(
+                Rect {
+                    top_left: pos,
+                    size: Size::new(block, block),
+                },
+                color,
+            );
+        }
+    }
+
+    pub fn draw_piece(&mut self, tetrimino: Tetrimino, pos: Point, greyed: bool) {
+        let block = (get_rotation(tetrimino) & 0xFFFF000000000000) >> 48;
+        let sprite = self.skin.get(Sprite::Tetrimino(tetrimino));
+        let size = self.layout.block;
+        for y in 0..4 {
+            let row = (block & (0xF000 >> (y * 4))) >> (12 - y * 4);
+            for x in 0..4 {
+                if (row >> (3 - x)) % 2 == 1 {
+                    let pos = pos + Point::new(x, y) * size as i32;
+                    if greyed {
+                        let mut target = Greyed(&mut self.buffer);
+                        sprite.draw(&mut target, pos, size).unwrap();
+                    } else {
+                        sprite.draw(&mut self.buffer, pos, size).unwrap();
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<S: Screen> Frontend for Ui<S>
+where
+    S::Error: Debug,
+{
+    fn set_skin(&mut self, skin: Skin) {
+        self.skin = skin;
+    }
+
+    fn set_layout(&mut self, settings: &Settings, players: u32) -> bool {
+        let layout = Layout::new(self.buffer.size(), settings, players);
+        let changed = layout != self.layout;
+        self.layout = layout;
+        changed
+    }
+
+    fn draw_background(&mut self) {
+        self.buffer.clear(Rgb888::BLACK).unwrap();
+        let block = self.layout.block;
+        let size = self.buffer.size();
+        for y in 0..size.height.div_ceil(block) {
+            for x in 0..size.width.div_ceil(block) {
+                let pos = Point::new((x * block) as i32, (y * block) as i32);
+                self.skin
+                    .get(Sprite::Wall)
+                    .draw(&mut self.buffer, pos, block)
+                    .unwrap();
+            }
+        }
+
+        for board in self.layout.boards.clone().iter() {
+            self.clear_panel(board.board);
+        }
+    }
+
+    fn draw_messages(&mut self, title: &str, lines: &[String]) {
+        let top_left = self.layout.messages.top_left;
+        let b = egrectangle!(
+            top_left = top_left,
+            bottom_right = (
+                self.layout.messages.bottom_right().x,
+                top_left.y + (lines.len() as i32 + 3) * 16
+            ),
+            style = primitive_style!(fill_color = Rgb888::BLACK)
+        );
+        b.draw(&mut self.buffer).unwrap();
+        self.draw_text(title, top_left + Point::new(8, 8), Rgb888::YELLOW);
+        for (idx, line) in lines.iter().enumerate() {
+            let pos = top_left + Point::new(8, 40 + idx as i32 * 16);
+            self.draw_text(line, pos, Rgb888::WHITE);
+        }
+    }
+
+    fn draw_menu(&mut self, title: &str, items: &[(String, String)], selected: usize, hint: &str) {
+        // Long lists (like video modes) only show a window around the selection
+        let first = selected
+            .saturating_sub(MENU_LINES / 2)
+            .min(items.len().saturating_sub(MENU_LINES));
+        let lines: Vec<String> = items
+            .iter()
+            .skip(first)
+            .take(MENU_LINES)
+            .map(|(name, value)| format!("{:<20}{}", name, value))
+            .collect();
+
+        let width = MENU_COLUMNS as i32 * 8;
+        let top_left = Point::new(
+            (self.buffer.size().width as i32 - width) / 2,
+            self.layout.messages.top_left.y,
+        );
+        let b = egrectangle!(
+            top_left = top_left,
+            bottom_right = top_left + Point::new(width, (lines.len() as i32 + 5) * 16),
+            style = primitive_style!(fill_color = Rgb888::BLACK)
+        );
+        b.draw(&mut self.buffer).unwrap();
+        self.draw_text(title, top_left + Point::new(8, 8), Rgb888::WHITE);
+        for (idx, line) in lines.iter().enumerate() {
+            let color = if first + idx == selected {
+                Rgb888::YELLOW
+            } else {
+                Rgb888::WHITE
+            };
+            let pos = top_left + Point::new(8, 40 + idx as i32 * 16);
+            self.draw_text(line, pos, color);
+        }
+        let pos = top_left + Point::new(8, 56 + lines.len() as i32 * 16);
+        self.draw_text(hint, pos, Rgb888::CYAN);
+    }
+
+    // The matrix and the lowest part of the buffer zone, with the falling piece and its ghost on top
+    fn draw_board(&mut self, board: usize, data: &GameData) {
+        self.draw_matrix(board, &data.matrix);
+        let layout = self.layout.boards[board];
+        self.fill_rect(
+            layout.buffer,
+            if data.danger() { DANGER } else { Rgb888::BLACK },
+        );
+
+        let mut cells = Vec::new();
+        for y in BUFFER_ROWS {
+            for x in 4..14 {
+                if let Some(mino) = data.matrix[y][x] {
+                    cells.push((mino, (x, y)));
+                }
+            }
+        }
+        if let GameState::Drop | GameState::Settings = data.state {
+            let (tetrimino, x, y, rot) = data.active_mino;
+            for cell in tetrimino.cells(data.ghost(), rot) {
+                cells.push((Tetrimino::Ghost, cell));
+            }
+            for cell in tetrimino.cells((x, y), rot) {
+                cells.push((tetrimino, cell));
+            }
+        }
+
+        // The buffer zone is only partially visible
+        let area = Rect {
+            top_left: layout.buffer.top_left,
+            size: layout.board.size + Size::new(0, layout.buffer.size.height),
+        };
+        let block = self.layout.block;
+        for (mino, cell) in cells {
+            if let Some(pos) = self.cell(board, cell) {
+                let mut target = Clipped {
+                    target: &mut self.buffer,
+                    area,
+                };
+                self.skin
+                    .get(Sprite::Tetrimino(mino))
+                    .draw(&mut target, pos, block)
+                    .unwrap();
+            }
+        }
+    }
+
+    fn draw_queue(&mut self, board: usize, queue: &VecDeque<Tetrimino>) {
+        let count = self.layout.previews;
+        if count == 0 {
+            return;
+        }
+        let rect = self.layout.boards[board].queue;
+        self.clear_panel(rect);
+        let block = self.layout.block as i32;
+        let spacing = (block * 3).min(rect.size.height as i32 / count as i32);
+        for (i, next) in queue.iter().take(count).enumerate() {
+            let pos = rect.top_left + Point::new(block / 2, block / 2 + i as i32 * spacing);
+            self.draw_piece(*next, pos, false);
+        }
+    }
+
+    // `locked` greys out the held piece while it can't be swapped
+    fn draw_hold(&mut self, board: usize, content: &Option<Tetrimino>, locked: bool) {
+        let rect = self.layout.boards[board].hold;
+        self.clear_panel(rect);
+        if let Some(tetrimino) = content {
+            let block = self.layout.block as i32;
+            let pos = rect.top_left + Point::new(block / 2, block / 2);
+            self.draw_piece(*tetrimino, pos, locked);
+        }
+    }
+
+    fn draw_stats(&mut self, board: usize, stats: &Stats) {
+        let rect = self.layout.boards[board].stats;
+        self.clear_panel(rect);
+        let font = self.panel_font();
+        let columns = (rect.size.width / font.width) as usize;
+        let lines = (rect.size.height / font.height) as usize;
+        // Names go above their values when both don't fit on a line,
+        // rows that don't fit below are left out
+        let height = if columns >= 10 { 1 } else { 2 };
+        let mut line = 0;
+        // Time has its own panel below the board
+        for (idx, (name, value)) in stats.rows().iter().skip(1).enumerate() {
+            // Piece distribution is set apart from the rest
+            if idx == 7 {
+                line += 1;
+            }
+            if line + height > lines {
+                break;
+            }
+            let pos = rect.top_left + Point::new(0, (line as u32 * font.height) as i32);
+            if height == 1 {
+                self.draw_panel_text(&format!("{:<6}{:>4}", name, value), pos, Rgb888::WHITE);
+            } else {
+                let right = columns.saturating_sub(value.len()) as u32 * font.width;
+                self.draw_panel_text(name, pos, Rgb888::WHITE);
+                let pos = pos + Point::new(right as i32, font.height as i32);
+                self.draw_panel_text(value, pos, Rgb888::WHITE);
+            }
+            line += height;
+        }
+        self.draw_timer(board, stats.time);
+    }
+
+    fn draw_garbage(&mut self, board: usize, lines: u32) {
+        let rect = self.layout.boards[board].meter;
+        self.clear_panel(rect);
+        let height = lines.min(20) * self.layout.block;
+        if height > 0 {
+            let top_left = rect.top_left + Point::new(0, (rect.size.height - height) as i32);
+            self.fill_rect(
+                Rect {
+                    top_left,
+                    size: Size::new(rect.size.width, height),
+                },
+                Rgb888::RED,
+            );
+        }
+    }
+
+    fn draw_timer(&mut self, board: usize, time: u64) {
+        let rect = self.layout.boards[board].timer;
+        self.clear_panel(rect);
+        let text = stats::format_time(time);
+        let font = self.panel_font();
+        let pos = rect.top_left
+            + Point::new(
+                (rect.size.width as i32 - (text.len() as u32 * font.width) as i32) / 2,
+                (rect.size.height as i32 - font.height as i32) / 2,
+            );
+        self.draw_panel_text(&text, pos, Rgb888::WHITE);
+    }
+
+    // Drawn on top of the matrix, which has to be redrawn every frame while effects are active
+    fn draw_effects(&mut self, board: usize, effects: &Effects, now: u64) {
+        let block = self.layout.block as i32;
+        for active in effects.iter() {
+            let progress = active.progress(now);
+            match &active.effect {
+                Effect::ClearRows(rows) => {
+                    // Flash for the first half, then dissolve from the center outwards
+                    let flash = progress < 128 && ((now - active.start) / 50).is_multiple_of(2);
+                    let gone = if progress < 128 {
+                        0
+                    } else {
+                        (progress - 128) * 6 / 128
+                    };
+                    for row in rows.iter() {
+                        for x in 4..14 {
+                            let distance = (if x < 9 { 9 - x } else { x - 8 }) as u32;
+                            let color = if distance <= gone {
+                                Rgb888::BLACK
+                            } else if flash || progress >= 128 {
+                                Rgb888::WHITE
+                            } else {
+                                continue;
+                            };
+                            self.fill_cell(board, (x, *row), color);
+                        }
+                    }
+                }
+                Effect::LockFlash(cells) => {
+                    for cell in cells.iter() {
+                        self.fill_cell(board, *cell, Rgb888::WHITE);
+                    }
+                }
+                Effect::Trail {
+                    columns,
+                    top,
+                    bottom,
+                } => {
+                    let level = (255 - progress) as u8 / 2;
+                    let color = Rgb888::new(level, level, level);
+                    for x in columns.iter() {
+                        for y in *bottom..(*top).min(VISIBLE_ROWS.end) {
+                            if let Some(pos) = self.cell(board, (*x, y)) {
+                                // A thin streak through the middle of the cell
+                                let r = egrectangle!(
+                                    top_left = pos + Point::new(block / 2 - 1, 0),
+                                    bottom_right = pos + Point::new(block / 2, block - 1),
+                                    style = primitive_style!(fill_color = color)
+                                );
+                                r.draw(&mut self.buffer).unwrap();
+                            }
+                        }
+                    }
+                }
+                Effect::Popup(lines) => {
+                    let rect = self.layout.boards[board].board;
+                    for (idx, line) in lines.iter().enumerate() {
+                        let pos = rect.top_left
+                            + Point::new(
+                                (rect.size.width as i32 - line.len() as i32 * 8) / 2,
+                                block * 5 + idx as i32 * 16,
+                            );
+                        self.draw_text(line, pos, Rgb888::YELLOW);
+                    }
+                }
+            }
+        }
+    }
+
+    fn refresh(&mut self) {
+        self.buffer.present();
+    }
+
+    fn video(&mut self) -> Option<&mut dyn Video> {
+        self.buffer.video()
+    }
+}
@@ -0,0 +1,83 @@
+use crate::game::{Clear, GameState};
+use crate::player::Player;
+use crate::replay::Input;
+use crate::settings::Settings;
+use alloc::vec::Vec;
+
+// Players on one clock, clears counter incoming garbage and send their attack to everyone else
+pub struct Versus {
+    pub players: Vec<Player>,
+    pub time: u64, // ms since the match started
+}
+
+impl Versus {
+    // Everyone gets the same pieces, but their own garbage holes
+    pub fn new(players: usize, seed: u64, settings: &Settings) -> Self {
+        Self::with_players(
+            (0..players)
+                .map(|idx| Player::new(seed, seed.wrapping_add(idx as u64 + 1), 0, settings))
+                .collect(),
+        )
+    }
+
+    // Players that start at time 0
+    pub fn with_players(players: Vec<Player>) -> Self {
+        Self { players, time: 0 }
+    }
+
+    // Moves the match clock on, the time in the stats stops once a player tops out
+    pub fn advance(&mut self, elapsed: u64) {
+        self.time += elapsed;
+        for player in self.players.iter_mut() {
+            if player.data.state != GameState::Die {
+                player.data.stats.time = self.time;
+            }
+        }
+    }
+
+    // The next timer that ran out, with the player it belongs to
+    pub fn due(&self) -> Option<(usize, Input)> {
+        self.players
+            .iter()
+            .enumerate()
+            .find_map(|(idx, player)| player.due(self.time).map(|input| (idx, input)))
+    }
+
+    pub fn input(&mut self, player: usize, input: Input, settings: &Settings) -> Option<Clear> {
+        if self.players[player].data.state == GameState::Die {
+            return None;
+        }
+        let clear = self.players[player].input(input, self.time, settings);
+        if let Some(clear) = clear.as_ref() {
+            let attack = self.players[player].attack(clear);
+            for (idx, other) in self.players.iter_mut().enumerate() {
+                if idx != player {
+                    other.garbage.add(attack, self.time);
+                }
+            }
+        }
+        clear
+    }
+
+    // Over once at most one player is left, or when the only player tops out
+    pub fn over(&self) -> bool {
+        self.alive().count() < self.players.len().min(2)
+    }
+
+    // The last one standing, `None` while the game runs or if everyone topped out together
+    pub fn winner(&self) -> Option<usize> {
+        let mut alive = self.alive();
+        match (alive.next(), alive.next()) {
+            (Some(idx), None) => Some(idx),
+            _ => None,
+        }
+    }
+
+    fn alive(&self) -> impl Iterator<Item = usize> + '_ {
+        self.players
+            .iter()
+            .enumerate()
+            .filter(|(_, player)| player.data.state != GameState::Die)
+            .map(|(idx, _)| idx)
+    }
+}
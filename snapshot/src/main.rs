@@ -0,0 +1,199 @@
+use rustris_engine::canvas::Canvas;
+use rustris_engine::frontend::Frontend;
+use rustris_engine::game::{GameData, GameState, Tetrimino};
+use rustris_engine::settings::Settings;
+use rustris_engine::stats::Stats;
+use rustris_engine::ui::Ui;
+use std::path::PathBuf;
+use std::{env, fs, process};
+
+struct Scene {
+    name: &'static str,
+    seed: u64,
+    hold: Option<Tetrimino>,
+    rows: &'static [&'static str], // top to bottom, ending at the floor
+    block: u32,
+    stats: bool, // fills in the stats panel and timer
+}
+
+const SCENES: [Scene; 4] = [
+    Scene {
+        name: "empty",
+        seed: 0,
+        hold: None,
+        rows: &[],
+        block: 16,
+        stats: false,
+    },
+    Scene {
+        name: "stack",
+        seed: 1,
+        hold: Some(Tetrimino::T),
+        rows: &[
+            "......ZZ..",
+            "L....ZZ...",
+            "LOO..JJJ.I",
+            "LOOSS..JTI",
+            "IIIISS.TTI",
+        ],
+        block: 16,
+        stats: false,
+    },
+    Scene {
+        name: "tspin",
+        seed: 2,
+        hold: Some(Tetrimino::I),
+        rows: &[
+            "..........",
+            "ZZ........",
+            "SZZ.......",
+            "SS.....OO.",
+            "JS.JJJ.OOL",
+            "JJ.IIIJLLL",
+            "LLL.OOJ.TT",
+            "LSSSOOJJTI",
+        ],
+        block: 16,
+        stats: false,
+    },
+    // The smallest blocks have less room for the panels than their text takes at 16
+    Scene {
+        name: "small",
+        seed: 3,
+        hold: Some(Tetrimino::O),
+        rows: &["JJ..T.....", "JIIIITT.SS", "JOO.LT.SSZ", "LOOLLL.ZZZ"],
+        block: 8,
+        stats: true,
+    },
+];
+
+fn main() {
+    let mut bless = false;
+    let mut dir = PathBuf::from("snapshot/golden");
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--bless" => bless = true,
+            _ => dir = PathBuf::from(arg),
+        }
+    }
+
+    if bless {
+        fs::create_dir_all(&dir).unwrap();
+    }
+
+    let mut failed = 0;
+    for scene in SCENES.iter() {
+        let canvas = render(scene);
+        let path = dir.join(format!("{}.ppm", scene.name));
+
+        if bless {
+            fs::write(&path, canvas.to_ppm()).unwrap();
+            println!("{}: written", scene.name);
+            continue;
+        }
+
+        match fs::read(&path)
+            .ok()
+            .and_then(|data| Canvas::from_ppm(&data))
+        {
+            Some(golden) if golden == canvas => println!("{}: ok", scene.name),
+            Some(golden) => {
+                let actual = dir.join(format!("{}.actual.ppm", scene.name));
+                fs::write(&actual, canvas.to_ppm()).unwrap();
+                println!(
+                    "{}: {} pixels differ, see {}",
+                    scene.name,
+                    canvas.diff(&golden),
+                    actual.display()
+                );
+                failed += 1;
+            }
+            None => {
+                println!("{}: no golden snapshot at {}", scene.name, path.display());
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+fn board(scene: &Scene) -> GameData {
+    let mut data = GameData::new(scene.seed);
+    for (idx, row) in scene.rows.iter().rev().enumerate() {
+        for (col, c) in row.chars().enumerate() {
+            data.matrix[4 + idx][4 + col] = parse_mino(c);
+        }
+    }
+    data.hold = scene.hold;
+    data
+}
+
+fn render(scene: &Scene) -> Canvas {
+    let mut data = board(scene);
+    data.get_piece().spawn(&mut data);
+    data.state = GameState::Drop;
+
+    let settings = Settings {
+        block: scene.block,
+        ..Settings::default()
+    };
+    let mut ui = Ui::new(Canvas::default(), &settings, 1);
+    ui.draw_board(0, &data);
+    ui.draw_hold(0, &data.hold, false);
+    ui.draw_queue(0, &data.queue);
+    if scene.stats {
+        data.stats = Stats {
+            time: 83_400,
+            lines: 12,
+            pieces: 41,
+            keys: 132,
+            attack: 9,
+            faults: 3,
+            distribution: [6, 6, 7, 5, 6, 6, 5],
+        };
+        ui.draw_stats(0, &data.stats);
+    }
+    ui.refresh();
+    ui.screen().clone()
+}
+
+fn parse_mino(c: char) -> Option<Tetrimino> {
+    match c {
+        'O' => Some(Tetrimino::O),
+        'I' => Some(Tetrimino::I),
+        'T' => Some(Tetrimino::T),
+        'L' => Some(Tetrimino::L),
+        'J' => Some(Tetrimino::J),
+        'S' => Some(Tetrimino::S),
+        'Z' => Some(Tetrimino::Z),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fails on any rendering change, rerun with `--bless` if it was intended
+    #[test]
+    fn scenes_match_golden() {
+        let dir = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/golden"));
+        for scene in SCENES.iter() {
+            let path = dir.join(format!("{}.ppm", scene.name));
+            let golden = fs::read(&path)
+                .ok()
+                .and_then(|data| Canvas::from_ppm(&data))
+                .unwrap_or_else(|| panic!("no golden snapshot at {}", path.display()));
+            let canvas = render(scene);
+            assert!(
+                canvas == golden,
+                "{}: {} pixels differ",
+                scene.name,
+                canvas.diff(&golden)
+            );
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use core::fmt::Write;
+use rustris_engine::text::{Color, Terminal};
+use uefi::proto::console::text::{self, Output};
+use uefi_services::system_table;
+
+// The firmware text console, used when there is no graphics output
+pub struct Console<'a> {
+    output: &'a mut Output<'a>,
+    size: (usize, usize),
+}
+
+impl Console<'_> {
+    pub fn new() -> Self {
+        let output = unsafe { system_table().as_ref().stdout() };
+        // The standard mode 0 is always 80x25
+        let size = output
+            .current_mode()
+            .unwrap()
+            .unwrap()
+            .map_or((80, 25), |mode| (mode.columns(), mode.rows()));
+        let _ = output.enable_cursor(false);
+        Self { output, size }
+    }
+}
+
+impl Terminal for Console<'_> {
+    fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    fn clear(&mut self) {
+        self.output
+            .set_color(text::Color::LightGray, text::Color::Black)
+            .unwrap()
+            .unwrap();
+        self.output.clear().unwrap().unwrap();
+    }
+
+    fn write(&mut self, x: usize, y: usize, text: &str, fg: Color, bg: Color) {
+        // Writing the bottom right corner would scroll the screen
+        let text = if y + 1 == self.size.1 && x + text.chars().count() >= self.size.0 {
+            &text[..text
+                .char_indices()
+                .nth(self.size.0.saturating_sub(x + 1))
+                .map_or(text.len(), |(idx, _)| idx)]
+        } else {
+            text
+        };
+        self.output.set_cursor_position(x, y).unwrap().unwrap();
+        self.output
+            .set_color(color(fg), background(bg))
+            .unwrap()
+            .unwrap();
+        self.output.write_str(text).unwrap();
+    }
+}
+
+fn color(color: Color) -> text::Color {
+    match color {
+        Color::Black => text::Color::Black,
+        Color::Blue => text::Color::Blue,
+        Color::Green => text::Color::Green,
+        Color::Cyan => text::Color::Cyan,
+        Color::Red => text::Color::Red,
+        Color::Magenta => text::Color::Magenta,
+        Color::Brown => text::Color::Brown,
+        Color::LightGray => text::Color::LightGray,
+        Color::DarkGray => text::Color::DarkGray,
+        Color::LightBlue => text::Color::LightBlue,
+        Color::LightGreen => text::Color::LightGreen,
+        Color::LightCyan => text::Color::LightCyan,
+        Color::LightRed => text::Color::LightRed,
+        Color::LightMagenta => text::Color::LightMagenta,
+        Color::Yellow => text::Color::Yellow,
+        Color::White => text::Color::White,
+    }
+}
+
+// Only the dark colours are valid backgrounds
+fn background(bg: Color) -> text::Color {
+    match bg {
+        Color::DarkGray => text::Color::LightGray,
+        Color::LightBlue => text::Color::Blue,
+        Color::LightGreen => text::Color::Green,
+        Color::LightCyan => text::Color::Cyan,
+        Color::LightRed => text::Color::Red,
+        Color::LightMagenta => text::Color::Magenta,
+        Color::Yellow => text::Color::Brown,
+        Color::White => text::Color::LightGray,
+        bg => color(bg),
+    }
+}
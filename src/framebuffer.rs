@@ -1,16 +1,49 @@
 use alloc::vec;
 use alloc::vec::Vec;
+use core::arch::x86_64::_rdtsc;
 use core::borrow::Borrow;
 use embedded_graphics::pixelcolor::Rgb888;
 use embedded_graphics::prelude::*;
 use log::info;
-use uefi::proto::console::gop::{BltOp, BltPixel, BltRegion, GraphicsOutput};
+use rustris_engine::frontend::{Video, VideoMode};
+use rustris_engine::settings::Renderer;
+use rustris_engine::ui::{Screen, HEIGHT, WIDTH};
+use uefi::proto::console::gop::{BltOp, BltPixel, BltRegion, GraphicsOutput, Mode, PixelFormat};
 use uefi_services::system_table;
 
+// Dirty regions are tracked in tiles of the screen
+const TILE: u32 = 16;
+// Above this many rectangles a single full blit is cheaper
+const MAX_DIRTY_RECTS: usize = 32;
+
+// Largest resolution considered when picking a mode on our own
+const MAX_RESOLUTION: (usize, usize) = (3840, 2160);
+
+fn video_mode(mode: &Mode) -> VideoMode {
+    VideoMode {
+        resolution: mode.info().resolution(),
+        format: format_name(mode.info().pixel_format()),
+    }
+}
+
+fn format_name(format: PixelFormat) -> &'static str {
+    match format {
+        PixelFormat::Rgb => "RGB",
+        PixelFormat::Bgr => "BGR",
+        PixelFormat::Bitmask => "Bitmask",
+        PixelFormat::BltOnly => "Blt only",
+    }
+}
+
 pub struct Framebuffer<'a> {
     buffer: Vec<BltPixel>,
     width: u32,
     height: u32,
+    tiles: (u32, u32),
+    dirty: Vec<bool>,
+    full_redraw: bool,
+    renderer: Renderer,
+    format: PixelFormat,
     gop: &'a mut GraphicsOutput<'a>,
 }
 
@@ -25,9 +58,11 @@ impl DrawTarget<Rgb888> for Framebuffer<'_> {
 
     fn draw_pixel(&mut self, item: Pixel<Rgb888>) -> Result<(), Self::Error> {
         let Pixel(coord, color) = item;
-        if coord.x < self.width as i32 && coord.y < self.height as i32 {
-            self.buffer[(coord.y * self.width as i32 + coord.x) as usize] =
+        if (0..self.width as i32).contains(&coord.x) && (0..self.height as i32).contains(&coord.y) {
+            let (x, y) = (coord.x as u32, coord.y as u32);
+            self.buffer[(y * self.width + x) as usize] =
                 BltPixel::new(color.r(), color.g(), color.b());
+            self.dirty[(y / TILE * self.tiles.0 + x / TILE) as usize] = true;
         }
         Ok(())
     }
@@ -41,40 +76,275 @@ impl DrawTarget<Rgb888> for Framebuffer<'_> {
             BltPixel::new(color.r(), color.g(), color.b());
             (self.width * (self.height + 1)) as usize
         ];
+        self.full_redraw = true;
         Ok(())
     }
 }
 
+impl Screen for Framebuffer<'_> {
+    fn present(&mut self) {
+        self.draw_buffer();
+    }
+
+    fn video(&mut self) -> Option<&mut dyn Video> {
+        Some(self)
+    }
+}
+
 impl Framebuffer<'_> {
-    pub fn new(width: u32, height: u32) -> Self {
+    // `None` if the firmware has no graphics output, like on a serial-only machine
+    pub fn new(preferred: (u32, u32), renderer: Renderer) -> Option<Self> {
         let protocol = unsafe {
             system_table()
                 .as_ref()
                 .boot_services()
                 .locate_protocol::<GraphicsOutput>()
-                .unwrap()
+                .ok()?
                 .unwrap()
         };
 
         let gop = unsafe { &mut *protocol.get() };
-        info!("Created framebuffer: {:?}", gop.current_mode_info());
-        Self {
-            buffer: vec![BltPixel::new(0, 0, 0); (width * (height + 1)) as usize],
-            width,
-            height,
-            gop,
+        if let Some(mode) = best_mode(gop, (preferred.0 as usize, preferred.1 as usize)) {
+            gop.set_mode(&mode).unwrap().unwrap();
         }
+
+        let mut framebuffer = Self {
+            buffer: Vec::new(),
+            width: 0,
+            height: 0,
+            tiles: (0, 0),
+            dirty: Vec::new(),
+            full_redraw: true,
+            renderer,
+            format: PixelFormat::BltOnly,
+            gop,
+        };
+        framebuffer.resize();
+        Some(framebuffer)
+    }
+
+    fn resize(&mut self) {
+        info!("Created framebuffer: {:?}", self.gop.current_mode_info());
+        let (width, height) = self.gop.current_mode_info().resolution();
+        self.width = width as u32;
+        self.height = height as u32;
+        self.format = self.gop.current_mode_info().pixel_format();
+
+        self.tiles = (
+            (self.width + TILE - 1) / TILE,
+            (self.height + TILE - 1) / TILE,
+        );
+        self.dirty = vec![false; (self.tiles.0 * self.tiles.1) as usize];
+        self.buffer = vec![BltPixel::new(0, 0, 0); (self.width * (self.height + 1)) as usize];
+        self.full_redraw = true;
+    }
+
+    // Modes without a linear frame buffer always go through Blt
+    fn linear(&self) -> bool {
+        self.renderer == Renderer::Linear && self.format != PixelFormat::BltOnly
     }
 
     pub fn draw_buffer(&mut self) {
-        self.gop
-            .blt(BltOp::BufferToVideo {
-                buffer: &self.buffer.borrow(),
-                src: BltRegion::Full,
-                dest: (0, 0),
-                dims: (800, 600),
+        let rects = self.dirty_rects();
+        if self.full_redraw || rects.len() > MAX_DIRTY_RECTS {
+            self.present((0, 0, self.width as usize, self.height as usize));
+        } else {
+            for (x, y, w, h) in rects {
+                // Tiles on the right and bottom edge may stick out of the screen
+                let (x, y) = (x * TILE, y * TILE);
+                self.present((
+                    x as usize,
+                    y as usize,
+                    (w * TILE).min(self.width - x) as usize,
+                    (h * TILE).min(self.height - y) as usize,
+                ));
+            }
+        }
+
+        self.full_redraw = false;
+        for tile in self.dirty.iter_mut() {
+            *tile = false;
+        }
+    }
+
+    // Copies the (x, y, width, height) region of the buffer to the screen
+    fn present(&mut self, (x, y, w, h): (usize, usize, usize, usize)) {
+        if self.linear() {
+            self.write_linear((x, y, w, h));
+        } else {
+            self.gop
+                .blt(BltOp::BufferToVideo {
+                    buffer: &self.buffer.borrow(),
+                    src: BltRegion::SubRectangle {
+                        coords: (x, y),
+                        px_stride: self.width as usize,
+                    },
+                    dest: (x, y),
+                    dims: (w, h),
+                })
+                .unwrap()
+                .unwrap();
+        }
+    }
+
+    fn write_linear(&mut self, (x, y, w, h): (usize, usize, usize, usize)) {
+        let stride = self.gop.current_mode_info().stride();
+        let format = self.format;
+        let mask = self.gop.current_mode_info().pixel_bitmask();
+        let mut frame_buffer = self.gop.frame_buffer();
+        let base = frame_buffer.as_mut_ptr() as *mut u32;
+
+        for row in y..y + h {
+            let src = &self.buffer[row * self.width as usize + x..][..w];
+            let dst = unsafe { base.add(row * stride + x) };
+            for (idx, pixel) in src.iter().enumerate() {
+                let value = match format {
+                    PixelFormat::Rgb => {
+                        pixel.red as u32 | (pixel.green as u32) << 8 | (pixel.blue as u32) << 16
+                    }
+                    PixelFormat::Bgr => {
+                        pixel.blue as u32 | (pixel.green as u32) << 8 | (pixel.red as u32) << 16
+                    }
+                    PixelFormat::Bitmask => {
+                        let mask = mask.unwrap();
+                        to_mask(pixel.red, mask.red)
+                            | to_mask(pixel.green, mask.green)
+                            | to_mask(pixel.blue, mask.blue)
+                    }
+                    PixelFormat::BltOnly => unreachable!(),
+                };
+                unsafe { dst.add(idx).write_volatile(value) };
+            }
+        }
+    }
+
+    fn time_frames(&mut self, frames: u64) -> u64 {
+        let full = (0, 0, self.width as usize, self.height as usize);
+        let start = unsafe { _rdtsc() };
+        for _ in 0..frames {
+            self.present(full);
+        }
+        (unsafe { _rdtsc() } - start) / frames
+    }
+
+    // Coalesces dirty tiles into (x, y, width, height) rectangles, measured in tiles.
+    // Runs within a row are merged first, then stacked onto identical runs of the row above.
+    fn dirty_rects(&self) -> Vec<(u32, u32, u32, u32)> {
+        let mut rects: Vec<(u32, u32, u32, u32)> = Vec::new();
+        let (columns, rows) = self.tiles;
+        for y in 0..rows {
+            let mut x = 0;
+            while x < columns {
+                if !self.dirty[(y * columns + x) as usize] {
+                    x += 1;
+                    continue;
+                }
+
+                let start = x;
+                while x < columns && self.dirty[(y * columns + x) as usize] {
+                    x += 1;
+                }
+
+                match rects
+                    .iter_mut()
+                    .find(|r| r.0 == start && r.2 == x - start && r.1 + r.3 == y)
+                {
+                    Some(rect) => rect.3 += 1,
+                    None => rects.push((start, y, x - start, 1)),
+                }
+            }
+        }
+        rects
+    }
+}
+
+// Picks the preferred resolution if the GOP offers it. Without one (0x0) the
+// current mode is kept unless it is too small to fit the canvas
+fn best_mode(gop: &GraphicsOutput, preferred: (usize, usize)) -> Option<Mode> {
+    let current = gop.current_mode_info().resolution();
+    if current == preferred {
+        return None;
+    }
+
+    let mut fallback: Option<Mode> = None;
+    for mode in gop.modes().map(|mode| mode.log()) {
+        let (width, height) = mode.info().resolution();
+        if (width, height) == preferred {
+            return Some(mode);
+        }
+        if width >= WIDTH as usize
+            && height >= HEIGHT as usize
+            && width <= MAX_RESOLUTION.0
+            && height <= MAX_RESOLUTION.1
+            && fallback.as_ref().map_or(true, |f| {
+                let (w, h) = f.info().resolution();
+                width * height > w * h
             })
-            .unwrap()
-            .unwrap();
+        {
+            fallback = Some(mode);
+        }
+    }
+
+    if current.0 >= WIDTH as usize && current.1 >= HEIGHT as usize {
+        None
+    } else {
+        fallback
+    }
+}
+
+// Scales an 8 bit channel to the full width of the bits selected by `mask`
+fn to_mask(value: u8, mask: u32) -> u32 {
+    if mask == 0 {
+        return 0;
+    }
+    let shift = mask.trailing_zeros();
+    let max = (mask >> shift) as u64;
+    ((value as u64 * max / 255) as u32) << shift
+}
+
+impl Video for Framebuffer<'_> {
+    fn modes(&self) -> Vec<VideoMode> {
+        self.gop
+            .modes()
+            .map(|mode| video_mode(&mode.log()))
+            .collect()
+    }
+
+    fn current_mode(&self) -> Option<usize> {
+        let info = self.gop.current_mode_info();
+        let current = VideoMode {
+            resolution: info.resolution(),
+            format: format_name(info.pixel_format()),
+        };
+        self.modes().iter().position(|mode| *mode == current)
+    }
+
+    fn set_mode(&mut self, index: usize) {
+        let mode = self.gop.modes().nth(index).unwrap().log();
+        self.gop.set_mode(&mode).unwrap().unwrap();
+        self.resize();
+    }
+
+    fn set_renderer(&mut self, renderer: Renderer) {
+        if self.renderer == renderer {
+            return;
+        }
+        self.renderer = renderer;
+        self.full_redraw = true;
+    }
+
+    // Presents the same full frame with both backends, returns TSC cycles per frame
+    fn benchmark(&mut self, frames: u64) -> (u64, Option<u64>) {
+        let renderer = self.renderer;
+        self.renderer = Renderer::Blt;
+        let blt = self.time_frames(frames);
+        self.renderer = Renderer::Linear;
+        let linear = if self.linear() {
+            Some(self.time_frames(frames))
+        } else {
+            None
+        };
+        self.renderer = renderer;
+        (blt, linear)
     }
 }
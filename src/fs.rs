@@ -0,0 +1,134 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use uefi::proto::loaded_image::LoadedImage;
+use uefi::proto::media::file::{
+    Directory, File, FileAttribute, FileInfo, FileMode, FileType, RegularFile,
+};
+use uefi::proto::media::fs::SimpleFileSystem;
+use uefi::Handle;
+use uefi_services::system_table;
+
+// FileInfo has to be 8 byte aligned
+#[repr(C, align(8))]
+struct InfoBuffer([u8; 1024]);
+
+// Files relative to the directory the game was loaded from
+pub struct Storage {
+    root: Directory,
+    dir: String,
+}
+
+impl Storage {
+    pub fn open(image: Handle) -> Option<Self> {
+        let boot_services = unsafe { system_table().as_ref().boot_services() };
+
+        let loaded_image = boot_services
+            .handle_protocol::<LoadedImage>(image)
+            .ok()?
+            .log();
+        let loaded_image = unsafe { &*loaded_image.get() };
+
+        let mut buffer = [0u8; 256];
+        let path = loaded_image.file_path(&mut buffer).ok()?;
+        let dir = match path.rfind('\\') {
+            Some(pos) => String::from(&path[..pos]),
+            None => String::new(),
+        };
+
+        let fs = boot_services
+            .handle_protocol::<SimpleFileSystem>(loaded_image.device())
+            .ok()?
+            .log();
+        let root = unsafe { &mut *fs.get() }.open_volume().ok()?.log();
+
+        Some(Self { root, dir })
+    }
+
+    fn path(&self, name: &str) -> String {
+        format!("{}\\{}", self.dir, name.replace('/', "\\"))
+    }
+
+    fn open_file(&mut self, name: &str, mode: FileMode) -> Option<RegularFile> {
+        let path = self.path(name);
+        let handle = self
+            .root
+            .open(&path, mode, FileAttribute::empty())
+            .ok()?
+            .log();
+        match handle.into_type().ok()?.log() {
+            FileType::Regular(file) => Some(file),
+            FileType::Dir(_) => None,
+        }
+    }
+
+    pub fn read(&mut self, name: &str) -> Option<Vec<u8>> {
+        let mut file = self.open_file(name, FileMode::Read)?;
+        let mut data = Vec::new();
+        let mut chunk = [0u8; 512];
+        loop {
+            let len = file.read(&mut chunk).ok()?.log();
+            if len == 0 {
+                break;
+            }
+            data.extend_from_slice(&chunk[..len]);
+        }
+        Some(data)
+    }
+
+    // Names of the subdirectories of `name`
+    pub fn list_dirs(&mut self, name: &str) -> Vec<String> {
+        self.list(name, true)
+    }
+
+    // Names of the files in directory `name`
+    pub fn list_files(&mut self, name: &str) -> Vec<String> {
+        self.list(name, false)
+    }
+
+    fn list(&mut self, name: &str, dirs: bool) -> Vec<String> {
+        let path = self.path(name);
+        let mut dir = match self
+            .root
+            .open(&path, FileMode::Read, FileAttribute::empty())
+            .ok()
+            .and_then(|handle| handle.log().into_type().ok())
+            .map(|file| file.log())
+        {
+            Some(FileType::Dir(dir)) => dir,
+            _ => return Vec::new(),
+        };
+
+        let mut names = Vec::new();
+        let mut buffer = InfoBuffer([0; 1024]);
+        while let Ok(entry) = dir.read_entry(&mut buffer.0) {
+            let info: &mut FileInfo = match entry.log() {
+                Some(info) => info,
+                None => break,
+            };
+            if info.attribute().contains(FileAttribute::DIRECTORY) != dirs {
+                continue;
+            }
+            let name: String =
+                core::char::decode_utf16(info.file_name().to_u16_slice().iter().cloned())
+                    .map(|c| c.unwrap_or(core::char::REPLACEMENT_CHARACTER))
+                    .collect();
+            if name != "." && name != ".." {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    pub fn write(&mut self, name: &str, data: &[u8]) -> bool {
+        // Delete the old file first, otherwise a shorter write leaves its tail behind
+        if let Some(file) = self.open_file(name, FileMode::ReadWrite) {
+            let _ = file.delete();
+        }
+
+        match self.open_file(name, FileMode::CreateReadWrite) {
+            Some(mut file) => file.write(data).is_ok(),
+            None => false,
+        }
+    }
+}
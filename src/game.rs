@@ -1,118 +1,122 @@
-use crate::ui::Ui;
-use alloc::collections::vec_deque::VecDeque;
+use crate::console::Console;
+use crate::framebuffer::Framebuffer;
+use crate::fs::Storage;
+use crate::net::Network;
+use crate::serial::SerialPort;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
-use core::borrow::BorrowMut;
 use core::ops::BitOr;
-use core::sync::atomic::{AtomicBool, Ordering};
-use log::info;
-use rand::rngs::SmallRng;
-use rand::seq::SliceRandom;
-use rand::SeedableRng;
-use uefi::proto::console::text::{Input, Key, ScanCode};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use log::{info, warn};
+use rustris_engine::ansi::{self, AnsiTerminal};
+use rustris_engine::bot::Bot;
+use rustris_engine::effects::{self, Effect, Effects};
+use rustris_engine::frontend::Frontend;
+use rustris_engine::fumen;
+use rustris_engine::game::{GameData, GameState, Tetrimino};
+use rustris_engine::garbage::Survival;
+use rustris_engine::menu::{Menu, MenuEvent, Page};
+use rustris_engine::net::{Event as NetEvent, Session};
+use rustris_engine::player::Player;
+use rustris_engine::puzzle::{Outcome, Puzzle};
+use rustris_engine::replay::{Input, Replay};
+use rustris_engine::settings::{self, Action, Display, KeyCode, Mode, Settings};
+use rustris_engine::sprites::Skin;
+use rustris_engine::stats;
+use rustris_engine::text::TextUi;
+use rustris_engine::ui::Ui;
+use rustris_engine::versus::Versus;
+use uefi::proto::console::text::{Input as TextInput, Key, ScanCode};
 use uefi::table::boot::{EventType, TimerTrigger, Tpl};
-use uefi::Event;
+use uefi::{Event, Handle};
 use uefi_services::system_table;
 
-pub type Matrix = [[Option<Tetrimino>; 18]; 44];
-static FALL_SPEED: u64 = 1000;
-static DROP_FLAG: AtomicBool = AtomicBool::new(false);
-static LOCKDOWN_FLAG: AtomicBool = AtomicBool::new(false);
-
-pub struct GameData {
-    pub active_mino: (Tetrimino, usize, usize, u8), // type, x, y, rot
-    pub queue: VecDeque<Vec<Tetrimino>>,
-    pub hold: Option<Tetrimino>,
-    pub hold_flag: bool,
-    pub state: GameState,
-    pub matrix: Matrix,
-    pub rng: SmallRng,
-}
-
-impl GameData {
-    pub fn get_piece(&mut self) -> Tetrimino {
-        while self.queue.len() < 2 {
-            self.push_bag();
-        }
-
-        if self.queue[0].is_empty() {
-            self.queue.pop_front();
-            self.push_bag();
-        }
-        self.queue[0].pop().unwrap()
-    }
-
-    fn push_bag(&mut self) {
-        let mut bag = vec![
-            Tetrimino::O,
-            Tetrimino::I,
-            Tetrimino::T,
-            Tetrimino::L,
-            Tetrimino::J,
-            Tetrimino::S,
-            Tetrimino::Z,
-        ];
-        bag.shuffle(&mut self.rng);
-        self.queue.push_back(bag);
-    }
-}
+static REVERT_FLAG: AtomicBool = AtomicBool::new(false);
+// Milliseconds since startup
+static CLOCK: AtomicU64 = AtomicU64::new(0);
+static CLOCK_INTERVAL: u64 = 10;
+static MODE_REVERT_TIMEOUT: u64 = 10 * 10_000_000;
+static BENCHMARK_FRAMES: u64 = 60;
+static SKIN_DIR: &str = "skins";
+static PUZZLE_DIR: &str = "puzzles";
+// The last finished game, in the same format the terminal version reads
+static REPLAY_FILE: &str = "last.rpl";
+// The board exported from the settings menu, as a fumen
+static EXPORT_FILE: &str = "board.fumen";
+// Characters per line when showing an exported fumen
+static EXPORT_LINE: usize = 64;
 
-#[derive(PartialEq, Eq, Copy, Clone)]
-pub enum GameState {
-    Spawn,
-    Drop,
-    ClearLines,
-    Die,
+pub struct Rustris<'a> {
+    player: Player, // marathon, its state also pauses every other mode while the menu is open
+    ui: Box<dyn Frontend + 'a>,
+    keyboard: &'a mut TextInput,
+    serial: Option<SerialPort<'a>>, // input side of the serial display
+    revert_event: Event,
+    last_tick: u64,
+    effects: Effects,
+    next_frame: u64,
+    clearing: Option<(GameData, u64)>, // the board as it locked, shown until the cleared rows are gone
+    settings: Settings,
+    storage: Option<Storage>,
+    menu: Option<Menu>,
+    previous_mode: Option<usize>,
+    loaded_skin: String,
+    replay: Replay,
+    versus: Option<Versus>,       // runs instead of `data` in versus mode
+    bot: Option<Bot>,             // plays player 2 in versus
+    survival: Option<Survival>,   // sends garbage to the only player of a one-player versus
+    network: Option<Network<'a>>, // opened by the first network game
+    online: Option<Online>,
+    puzzles: Option<Puzzles>,
 }
 
-pub struct Rustris<'a> {
-    data: GameData,
-    ui: Ui<'a>,
-    keyboard: &'a mut Input,
-    drop_event: Event,
-    lockdown_event: Event,
-    waiting_lockdown: bool,
+// The puzzle list, a puzzle is played as a one-player versus
+struct Puzzles {
+    list: Vec<Puzzle>,
+    errors: Vec<String>, // files that could not be loaded
+    selected: usize,
+    outcome: Option<Outcome>, // of the puzzle being played, once decided
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
-pub enum Tetrimino {
-    O,
-    I,
-    T,
-    L,
-    J,
-    S,
-    Z,
-    Ghost,
+// A match against another machine, its board is never seen, only its garbage
+struct Online {
+    session: Session,
+    player: Option<Player>, // once an opponent is found
 }
 
-impl Rustris<'_> {
-    pub fn new() -> Self {
-        let drop_event = unsafe {
+impl<'a> Rustris<'a> {
+    pub fn new(image: Handle) -> Self {
+        let revert_event = unsafe {
             system_table()
                 .as_ref()
                 .boot_services()
                 .create_event(
                     EventType::TIMER.bitor(EventType::NOTIFY_SIGNAL),
                     Tpl::NOTIFY,
-                    Some(tick_piece),
+                    Some(tick_revert),
                 )
                 .unwrap()
                 .unwrap()
         };
 
-        let lockdown_event = unsafe {
-            system_table()
-                .as_ref()
-                .boot_services()
+        unsafe {
+            let boot_services = system_table().as_ref().boot_services();
+            let clock_event = boot_services
                 .create_event(
                     EventType::TIMER.bitor(EventType::NOTIFY_SIGNAL),
                     Tpl::NOTIFY,
-                    Some(tick_lockdown),
+                    Some(tick_clock),
                 )
                 .unwrap()
+                .unwrap();
+            boot_services
+                .set_timer(clock_event, TimerTrigger::Periodic(CLOCK_INTERVAL * 10000))
                 .unwrap()
-        };
+                .unwrap();
+        }
 
         let seed = unsafe {
             system_table()
@@ -126,36 +130,96 @@ impl Rustris<'_> {
 
         info!("Seed: {}", seed);
 
-        let data = GameData {
-            active_mino: (Tetrimino::O, 0, 0, 0),
-            queue: VecDeque::new(),
-            hold: None,
-            hold_flag: false,
-            state: GameState::Spawn,
-            matrix: [[None; 18]; 44], // + 4 buffer blocks on each side
-            rng: SmallRng::seed_from_u64(seed as u64),
-        };
-
         let protocol = unsafe {
             system_table()
                 .as_ref()
                 .boot_services()
-                .locate_protocol::<Input>()
+                .locate_protocol::<TextInput>()
                 .unwrap()
                 .unwrap()
         };
 
         let keyboard = unsafe { &mut *protocol.get() };
-        let mut ui = Ui::init();
-        ui.draw_hold(&None);
-        Self {
-            data,
+
+        let mut storage = Storage::open(image);
+        if storage.is_none() {
+            warn!("Could not open boot volume, settings will not be saved");
+        }
+        let (settings, mut errors) =
+            match storage.as_mut().and_then(|s| s.read(settings::FILE_NAME)) {
+                Some(src) => Settings::parse(&String::from_utf8_lossy(&src)),
+                None => (Settings::default(), Vec::new()),
+            };
+
+        let framebuffer = match settings.display {
+            Display::Auto => Framebuffer::new((settings.width, settings.height), settings.renderer),
+            _ => None,
+        };
+        let serial = match settings.display {
+            Display::Serial => SerialPort::open(),
+            _ => None,
+        };
+        let ui: Box<dyn Frontend + 'a> = match (framebuffer, &serial) {
+            (Some(framebuffer), _) => Box::new(Ui::new(framebuffer, &settings, 1)),
+            (None, Some(serial)) => {
+                let terminal = AnsiTerminal::new(serial.share(), ansi::SIZE);
+                Box::new(TextUi::new(terminal, &settings, 1))
+            }
+            (None, None) => {
+                match settings.display {
+                    Display::Auto => warn!("No graphics output, falling back to text mode"),
+                    Display::Serial => warn!("No serial port, falling back to text mode"),
+                    Display::Text => {}
+                }
+                Box::new(TextUi::new(Console::new(), &settings, 1))
+            }
+        };
+        let player = Player::new(seed as u64, seed as u64, 0, &settings);
+        let mut game = Self {
+            player,
             ui,
             keyboard,
-            drop_event,
-            lockdown_event,
-            waiting_lockdown: false,
+            serial,
+            revert_event,
+            last_tick: 0,
+            effects: Effects::default(),
+            next_frame: 0,
+            clearing: None,
+            settings,
+            storage,
+            menu: None,
+            previous_mode: None,
+            loaded_skin: String::from(settings::DEFAULT_SKIN),
+            replay: Replay::new(seed as u64),
+            versus: None,
+            bot: None,
+            survival: None,
+            network: None,
+            online: None,
+            puzzles: None,
+        };
+
+        if let Err(e) = game.load_skin() {
+            errors.push(e);
+        }
+        if !errors.is_empty() {
+            for error in errors.iter() {
+                warn!("{}: {}", settings::FILE_NAME, error);
+            }
+            game.ui
+                .draw_messages("Invalid settings, press any key", &errors);
+            game.ui.refresh();
+            game.wait_key();
+            game.ui.draw_background();
+        }
+        if game.settings.mode != Mode::Marathon {
+            game.new_game();
+        } else {
+            draw_player(&mut *game.ui, 0, &game.player);
+            game.ui.refresh();
         }
+        game.last_tick = CLOCK.load(Ordering::Relaxed);
+        game
     }
 
     pub fn start(&mut self) -> ! {
@@ -165,415 +229,1047 @@ impl Rustris<'_> {
     }
 
     fn step(&mut self) {
-        match self.data.state {
-            GameState::Spawn => {
-                let tetrimino = self.data.get_piece();
-                self.ui.draw_queue(&self.data.queue);
-                let res = tetrimino.spawn(&mut self.data);
-                self.ui.refresh();
-                if res {
-                    self.data.state = GameState::Die;
-                } else {
-                    self.data.state = GameState::Drop;
-                    DROP_FLAG.store(true, Ordering::Relaxed);
-                }
+        self.advance_clock();
+        if self.player.data.state != GameState::Settings {
+            if self.puzzles.is_some() && self.versus.is_none() {
+                self.step_puzzle_list();
+                return;
             }
-            GameState::Drop => {
-                if DROP_FLAG.load(Ordering::Relaxed) {
-                    self.move_piece_no_check((0, -1));
-                    self.ui.draw_matrix(&self.data.matrix);
-                    self.ui.refresh();
+            if self.versus.is_some() {
+                self.step_versus();
+                return;
+            }
+            if self.online.is_some() {
+                self.step_online();
+                return;
+            }
+        }
+        self.animate();
+        match self.player.data.state {
+            GameState::Settings => {
+                if REVERT_FLAG.swap(false, Ordering::Relaxed) {
+                    let event = self.menu.as_mut().unwrap().revert_mode();
+                    self.handle_menu_event(event);
                 }
 
-                if LOCKDOWN_FLAG.load(Ordering::Relaxed) {
-                    LOCKDOWN_FLAG.store(false, Ordering::Relaxed);
-                    self.manage_lockdown_timer(true);
-                    self.data.state = GameState::ClearLines;
+                if let Some(key) = self.read_key() {
+                    let modes = self.ui.video().map_or(0, |video| video.modes().len());
+                    let menu = self.menu.as_mut().unwrap();
+                    let event = menu.handle(key, &mut self.settings, modes);
+                    self.handle_menu_event(event);
                 }
+            }
+            GameState::Die => self.show_results(),
+            _ => self.step_marathon(),
+        }
+    }
+
+    // Runs off the game clock like the other modes, keys wait while cleared rows are animating
+    fn step_marathon(&mut self) {
+        if let Some((_, until)) = self.clearing.as_ref() {
+            if CLOCK.load(Ordering::Relaxed) < *until {
+                return;
+            }
+            self.clearing = None;
+            self.ui.draw_board(0, &self.player.data);
+            self.ui.refresh();
+        }
+
+        while let Some(input) = self.player.due(self.player.data.stats.time) {
+            self.marathon_input(input);
+            if self.clearing.is_some() {
+                return;
+            }
+        }
+
+        if let Some(key) = self.read_key() {
+            if key == KeyCode::Escape {
+                self.open_settings();
+                return;
+            }
+            if let Some(action) = self.settings.action(key) {
+                self.player.data.stats.keys += 1;
+                self.marathon_input(Input::Action(action));
+            }
+        }
+    }
 
-                if let Some(key) = self.keyboard.read_key().unwrap().unwrap() {
-                    match key {
-                        Key::Special(ScanCode::LEFT) => self.move_piece_no_check((-1, 0)),
-                        Key::Special(ScanCode::RIGHT) => self.move_piece_no_check((1, 0)),
-                        Key::Special(ScanCode::DOWN) => self.move_piece_no_check((0, -1)),
-                        Key::Printable(e) => match e.into() {
-                            ' ' => {
-                                LOCKDOWN_FLAG.store(true, Ordering::Relaxed);
-                                while !self.move_piece((0, -1)) {}
-                            }
-                            'e' => self.rotate_piece(1),
-                            'q' => self.rotate_piece(3),
-                            'f' => {
-                                self.hold_piece();
-                                self.ui.draw_hold(&self.data.hold);
-                                self.ui.draw_queue(&self.data.queue);
-                            }
-                            _ => {}
-                        },
-                        _ => {}
+    fn marathon_input(&mut self, input: Input) {
+        let time = self.player.data.stats.time;
+        let before = self.player.data.active_mino;
+        let (tetrimino, x, y, rot) = before;
+        // The rules clear the rows right away, the animation needs them as they locked
+        let locked = match input {
+            Input::Lock if self.settings.animations => {
+                let mut locked = self.player.data.clone();
+                locked.lock();
+                locked.state = GameState::ClearLines;
+                Some(locked)
+            }
+            _ => None,
+        };
+        let clear = self.player.input(input, time, &self.settings);
+        // Gravity on a grounded piece changes nothing, the replay can do without it
+        if input != Input::Gravity || self.player.data.active_mino != before {
+            self.replay.record(time, input);
+        }
+        draw_input(&mut *self.ui, 0, &self.player, input);
+
+        let now = CLOCK.load(Ordering::Relaxed);
+        match input {
+            Input::Action(Action::HardDrop) if self.settings.animations => {
+                self.add_trail(tetrimino, (x, y), rot);
+            }
+            Input::Lock if self.player.data.state != GameState::Die => {
+                if let Some(locked) = locked {
+                    let cells = tetrimino.cells((x, y), rot);
+                    self.effects
+                        .add(Effect::LockFlash(cells), now, effects::LOCK_FLASH);
+                    let text = clear.map(|clear| clear.text()).unwrap_or_default();
+                    if !text.is_empty() {
+                        self.effects.add(Effect::Popup(text), now, effects::POPUP);
+                    }
+                    let rows = locked.full_rows();
+                    if !rows.is_empty() {
+                        self.effects
+                            .add(Effect::ClearRows(rows), now, effects::LINE_CLEAR_DELAY);
+                        self.ui.draw_board(0, &locked);
+                        self.clearing = Some((locked, now + effects::LINE_CLEAR_DELAY));
                     }
-                    self.ui.draw_matrix(&self.data.matrix);
-                    self.ui.refresh();
                 }
             }
-            GameState::ClearLines => {
-                'outer: loop {
-                    let clone = self.data.matrix;
-                    for (idx, row) in clone.iter().enumerate() {
-                        if row.iter().skip(4).take(10).all(|e| e.is_some()) {
-                            for y in idx..39 {
-                                self.data.matrix[y] = self.data.matrix[y + 1];
-                                if self.data.matrix[y + 1].iter().all(|e| e.is_none()) {
-                                    break;
-                                }
-                            }
-                            continue 'outer;
-                        }
-                    }
-                    break 'outer;
+            _ => {}
+        }
+        self.ui.refresh();
+    }
+
+    // Adds the time since the last step to the game time, unless the game is paused
+    fn advance_clock(&mut self) {
+        let now = CLOCK.load(Ordering::Relaxed);
+        let elapsed = now - self.last_tick;
+        self.last_tick = now;
+        if self.player.data.state == GameState::Settings {
+            return;
+        }
+        // Nothing runs while the puzzle list is open
+        if self.puzzles.is_some() && self.versus.is_none() {
+            return;
+        }
+
+        if let Some(versus) = self.versus.as_mut() {
+            let shown = versus.time / 100;
+            versus.advance(elapsed);
+            if versus.time / 100 != shown {
+                for (idx, player) in versus.players.iter().enumerate() {
+                    self.ui.draw_timer(idx, player.data.stats.time);
                 }
-                self.ui.draw_matrix(&self.data.matrix);
                 self.ui.refresh();
-                self.data.state = GameState::Spawn;
             }
-            GameState::Die => {
-                panic!("u ded lol");
+            return;
+        }
+
+        if let Some(online) = self.online.as_mut() {
+            if let Some(player) = online.player.as_mut() {
+                let stats = &mut player.data.stats;
+                let shown = stats.time / 100;
+                stats.time += elapsed;
+                if stats.time / 100 != shown {
+                    self.ui.draw_timer(0, stats.time);
+                    self.ui.refresh();
+                }
+            }
+            return;
+        }
+
+        let stats = &mut self.player.data.stats;
+        let shown = stats.time / 100;
+        stats.time += elapsed;
+        if stats.time / 100 != shown {
+            self.ui.draw_timer(0, stats.time);
+            self.ui.refresh();
+        }
+    }
+
+    // Redraws the matrix with the active effects on top at a fixed frame rate
+    fn animate(&mut self) {
+        if self.effects.is_empty() || self.player.data.state == GameState::Settings {
+            return;
+        }
+        let now = CLOCK.load(Ordering::Relaxed);
+        if now < self.next_frame {
+            return;
+        }
+        self.next_frame = now + effects::FRAME;
+        self.effects.update(now);
+        let data = match self.clearing.as_ref() {
+            Some((locked, _)) => locked,
+            None => &self.player.data,
+        };
+        self.ui.draw_board(0, data);
+        self.ui.draw_effects(0, &self.effects, now);
+        self.ui.refresh();
+    }
+
+    // Streaks from where a hard dropped piece was to where it landed
+    fn add_trail(&mut self, tetrimino: Tetrimino, from: (usize, usize), rot: u8) {
+        let (_, x, y, _) = self.player.data.active_mino;
+        let before = tetrimino.cells(from, rot);
+        let top = before.iter().map(|c| c.1).max().unwrap() + 1;
+        let bottom = tetrimino
+            .cells((x, y), rot)
+            .iter()
+            .map(|c| c.1)
+            .max()
+            .unwrap()
+            + 1;
+        let mut columns: Vec<usize> = before.iter().map(|c| c.0).collect();
+        columns.sort_unstable();
+        columns.dedup();
+
+        let trail = Effect::Trail {
+            columns,
+            top,
+            bottom,
+        };
+        self.effects
+            .add(trail, CLOCK.load(Ordering::Relaxed), effects::TRAIL);
+    }
+
+    fn show_results(&mut self) {
+        let lines: Vec<String> = self
+            .player
+            .data
+            .stats
+            .rows()
+            .iter()
+            .map(|(name, value)| format!("{:<8}{}", name, value))
+            .collect();
+        info!("Game over: {:?}", lines);
+        let saved = match self.storage.as_mut() {
+            Some(storage) => storage.write(REPLAY_FILE, self.replay.serialize().as_bytes()),
+            None => false,
+        };
+        if !saved {
+            warn!("Failed to write {}", REPLAY_FILE);
+        }
+        self.ui
+            .draw_messages("Game over, press any key to play again", &lines);
+        self.ui.refresh();
+        self.wait_key();
+        self.new_game();
+    }
+
+    // Starts over in the mode from the settings
+    fn new_game(&mut self) {
+        self.effects.clear();
+        self.clearing = None;
+        let seed = CLOCK.load(Ordering::Relaxed);
+        self.player = Player::new(seed, seed, 0, &self.settings);
+        self.replay = Replay::new(seed);
+        self.versus = None;
+        self.bot = None;
+        self.survival = None;
+        self.online = None;
+        self.puzzles = None;
+        match self.settings.mode {
+            Mode::Marathon => {}
+            Mode::Versus => {
+                self.versus = Some(Versus::new(2, seed, &self.settings));
+                self.bot = self.settings.cpu.map(Bot::new);
             }
+            Mode::Network => self.online = self.open_session(seed),
+            Mode::Survival => {
+                self.versus = Some(Versus::new(1, seed, &self.settings));
+                self.survival = Some(Survival::new(0));
+            }
+            Mode::Puzzle => self.puzzles = Some(self.load_puzzles()),
+        }
+        self.ui.set_layout(&self.settings, self.players());
+        self.ui.draw_background();
+        match self.mode() {
+            Mode::Marathon => draw_player(&mut *self.ui, 0, &self.player),
+            Mode::Versus | Mode::Survival => self.draw_versus(),
+            Mode::Network => self.draw_online(),
+            Mode::Puzzle => self.draw_puzzle_list(),
+        }
+        self.ui.refresh();
+        self.last_tick = CLOCK.load(Ordering::Relaxed);
+    }
+
+    // The mode of the running game, the settings may already say otherwise
+    fn mode(&self) -> Mode {
+        if self.puzzles.is_some() {
+            Mode::Puzzle
+        } else if self.survival.is_some() {
+            Mode::Survival
+        } else if self.versus.is_some() {
+            Mode::Versus
+        } else if self.online.is_some() {
+            Mode::Network
+        } else {
+            Mode::Marathon
         }
     }
 
-    fn move_ghost(&mut self, delete: bool) {
-        let ghost_mino = Tetrimino::Ghost;
-        let (_, x, y, rot) = self.data.active_mino;
-        let mut counter = 4;
-        while !ghost_mino.place(&mut self.data, (x, y - counter), rot, false, true) {
-            ghost_mino.place(&mut self.data, (x, y - counter + 1), rot, true, false);
-            ghost_mino.place(&mut self.data, (x, y - counter), rot, delete, false);
-            counter += 1;
+    fn players(&self) -> u32 {
+        self.versus
+            .as_ref()
+            .map_or(1, |versus| versus.players.len() as u32)
+    }
+
+    // Falls back to marathon without a network card
+    fn open_session(&mut self, seed: u64) -> Option<Online> {
+        if self.network.is_none() {
+            self.network = Network::open();
+        }
+        match self.network.as_ref() {
+            Some(network) => Some(Online {
+                session: Session::new(network.id(), seed),
+                player: None,
+            }),
+            None => {
+                warn!("No network card, falling back to marathon");
+                self.settings.mode = Mode::Marathon;
+                None
+            }
         }
     }
 
-    fn hold_piece(&mut self) {
-        if !self.data.hold_flag {
-            let (old_mino, x, y, rot) = self.data.active_mino;
-            self.move_ghost(true);
-            old_mino.place(&mut self.data, (x, y), rot, true, false);
-            if let Some(tetrimino) = self.data.hold {
-                tetrimino.spawn(&mut self.data);
+    // Versus and survival run entirely off the clock
+    fn step_versus(&mut self) {
+        while let Some((idx, input)) = self.versus.as_ref().unwrap().due() {
+            self.versus_input(idx, input);
+        }
+
+        if let Some(survival) = self.survival.as_mut() {
+            let versus = self.versus.as_mut().unwrap();
+            let lines = survival.due(versus.time);
+            if lines > 0 {
+                let player = &mut versus.players[0];
+                player.garbage.add(lines, versus.time);
+                self.ui.draw_garbage(0, player.garbage.pending());
+                self.ui.refresh();
+            }
+        }
+
+        if let Some(bot) = self.bot.as_mut() {
+            let versus = self.versus.as_mut().unwrap();
+            let player = &mut versus.players[1];
+            if let Some(action) = bot.next(&player.data, versus.time) {
+                player.data.stats.keys += 1;
+                self.versus_input(1, Input::Action(action));
+            }
+        }
+
+        if let Some(key) = self.read_key() {
+            if key == KeyCode::Escape {
+                self.open_settings();
+                return;
+            }
+            if let Some((idx, action)) = self.settings.player_action(key) {
+                // Player 2's keys do nothing while the CPU plays
+                if idx > 0 && self.bot.is_some() {
+                    return;
+                }
+                let versus = self.versus.as_mut().unwrap();
+                if let Some(player) = versus.players.get_mut(idx) {
+                    player.data.stats.keys += 1;
+                    self.versus_input(idx, Input::Action(action));
+                }
+            }
+        }
+
+        let outcome = self.puzzles.as_ref().and_then(|puzzles| puzzles.outcome);
+        if let Some(outcome) = outcome {
+            self.show_puzzle_results(outcome);
+            return;
+        }
+        if self.versus.as_ref().unwrap().over() {
+            if self.puzzles.is_some() {
+                // Topped out on spawning the first piece
+                self.show_puzzle_results(Outcome::Failed);
+            } else if self.survival.is_some() {
+                self.show_survival_results();
             } else {
-                self.data.get_piece().spawn(&mut self.data);
+                self.show_versus_results();
+            }
+        }
+    }
+
+    fn versus_input(&mut self, idx: usize, input: Input) {
+        let versus = self.versus.as_mut().unwrap();
+        let clear = versus.input(idx, input, &self.settings);
+        if let (Some(puzzles), Input::Lock) = (self.puzzles.as_mut(), input) {
+            let puzzle = &puzzles.list[puzzles.selected];
+            puzzles.outcome = puzzle.check(&versus.players[idx].data, clear.as_ref());
+        }
+        draw_input(&mut *self.ui, idx, &versus.players[idx], input);
+        if input == Input::Lock {
+            // The attack changes everyone else's meter
+            for (idx, player) in versus.players.iter().enumerate() {
+                self.ui.draw_garbage(idx, player.garbage.pending());
             }
-            self.data.hold = Some(old_mino);
-            self.data.hold_flag = true;
-            DROP_FLAG.store(true, Ordering::Relaxed);
-            self.ui.refresh();
         }
+        self.ui.refresh();
     }
 
-    fn rotate_piece(&mut self, drot: u8) {
-        let active_mino = self.data.active_mino;
+    fn draw_versus(&mut self) {
+        let versus = self.versus.as_ref().unwrap();
+        for (idx, player) in versus.players.iter().enumerate() {
+            draw_player(&mut *self.ui, idx, player);
+        }
+    }
 
-        let (tetrimino, x, y, rot) = (
-            active_mino.0,
-            active_mino.1,
-            active_mino.2,
-            (active_mino.3 + drot) % 4,
-        );
-        self.move_ghost(true);
-        if tetrimino.place(&mut self.data, (x, y - 1), rot, false, true) {
-            self.manage_lockdown_timer(false);
-        } else {
-            self.manage_lockdown_timer(true);
+    // The opponent's game only shows up as garbage, the session runs on real time
+    fn step_online(&mut self) {
+        let now = CLOCK.load(Ordering::Relaxed);
+        let online = self.online.as_mut().unwrap();
+        let events = online.session.update(self.network.as_mut().unwrap(), now);
+        for event in events {
+            match event {
+                NetEvent::Start { seed } => {
+                    info!("Opponent found, seed: {}", seed);
+                    let player = Player::new(seed, now, 0, &self.settings);
+                    self.online.as_mut().unwrap().player = Some(player);
+                    self.ui.draw_background();
+                    self.draw_online();
+                    self.ui.refresh();
+                }
+                NetEvent::Garbage(lines) => {
+                    let online = self.online.as_mut().unwrap();
+                    if let Some(player) = online.player.as_mut() {
+                        player.garbage.add(lines, player.data.stats.time);
+                        self.ui.draw_garbage(0, player.garbage.pending());
+                        self.ui.refresh();
+                    }
+                }
+                NetEvent::Won => return self.show_online_results("You win"),
+                NetEvent::Disconnected => return self.show_online_results("Opponent disconnected"),
+            }
         }
 
-        tetrimino.rotate(&mut self.data, (x, y), active_mino.3, rot);
-        self.move_ghost(false);
+        if self.online.as_ref().unwrap().player.is_none() {
+            if self.read_key() == Some(KeyCode::Escape) {
+                self.open_settings();
+            }
+            return;
+        }
+
+        loop {
+            let player = self.online.as_ref().unwrap().player.as_ref().unwrap();
+            match player.due(player.data.stats.time) {
+                Some(input) => self.online_input(input),
+                None => break,
+            }
+        }
+
+        if let Some(key) = self.read_key() {
+            // There is no pausing a network game
+            if key == KeyCode::Escape {
+                self.online.as_mut().unwrap().session.lose();
+                return self.show_online_results("You gave up");
+            }
+            if let Some(action) = self.settings.action(key) {
+                let online = self.online.as_mut().unwrap();
+                online.player.as_mut().unwrap().data.stats.keys += 1;
+                self.online_input(Input::Action(action));
+            }
+        }
+
+        let online = self.online.as_mut().unwrap();
+        if online.player.as_ref().unwrap().data.state == GameState::Die {
+            online.session.lose();
+            self.show_online_results("You lose");
+        }
+    }
+
+    fn online_input(&mut self, input: Input) {
+        let online = self.online.as_mut().unwrap();
+        let player = online.player.as_mut().unwrap();
+        let clear = player.input(input, player.data.stats.time, &self.settings);
+        if let Some(clear) = clear.as_ref() {
+            online.session.send_garbage(player.attack(clear));
+        }
+        draw_input(&mut *self.ui, 0, player, input);
+        self.ui.refresh();
+    }
+
+    fn draw_online(&mut self) {
+        match self.online.as_ref().unwrap().player.as_ref() {
+            Some(player) => draw_player(&mut *self.ui, 0, player),
+            None => self
+                .ui
+                .draw_messages("Searching for an opponent, Esc: settings", &[]),
+        }
     }
 
-    fn move_piece_no_check(&mut self, dpos: (isize, isize)) {
-        self.move_piece(dpos);
+    // Keeps the session going until a key is pressed, so the other side hears how the game ended
+    fn show_online_results(&mut self, title: &str) {
+        let player = self.online.as_ref().unwrap().player.as_ref().unwrap();
+        let lines: Vec<String> = player
+            .data
+            .stats
+            .rows()
+            .iter()
+            .map(|(name, value)| format!("{:<8}{}", name, value))
+            .collect();
+        info!("Network game over: {}", title);
+        self.ui
+            .draw_messages(&format!("{}, press any key to play again", title), &lines);
+        self.ui.refresh();
+        loop {
+            let now = CLOCK.load(Ordering::Relaxed);
+            let online = self.online.as_mut().unwrap();
+            online.session.update(self.network.as_mut().unwrap(), now);
+            if self.read_key().is_some() {
+                break;
+            }
+        }
+        self.new_game();
     }
 
-    fn move_piece(&mut self, dpos: (isize, isize)) -> bool {
-        let active_mino = self.data.active_mino;
+    // Both players' stats side by side, any key starts a rematch
+    fn show_versus_results(&mut self) {
+        let versus = self.versus.as_ref().unwrap();
+        let title = match versus.winner() {
+            Some(1) if self.bot.is_some() => String::from("CPU wins, press any key for a rematch"),
+            Some(idx) => format!("Player {} wins, press any key for a rematch", idx + 1),
+            None => String::from("Draw, press any key for a rematch"),
+        };
+        let rows: Vec<Vec<(&str, String)>> = versus
+            .players
+            .iter()
+            .map(|player| player.data.stats.rows())
+            .collect();
+        let mut lines = vec![format!("{:<8}{:<10}{}", "", "P1", "P2")];
+        for (row, (name, value)) in rows[0].iter().enumerate() {
+            lines.push(format!("{:<8}{:<10}{}", name, value, rows[1][row].1));
+        }
+        info!("Versus over: {}", title);
+        self.ui.draw_messages(&title, &lines);
+        self.ui.refresh();
+        self.wait_key();
+        self.new_game();
+    }
 
-        let (tetrimino, x, y, rot) = (
-            active_mino.0,
-            (active_mino.1 as isize + dpos.0) as usize,
-            (active_mino.2 as isize + dpos.1) as usize,
-            active_mino.3,
+    fn show_survival_results(&mut self) {
+        let player = &self.versus.as_ref().unwrap().players[0];
+        let lines: Vec<String> = player
+            .data
+            .stats
+            .rows()
+            .iter()
+            .map(|(name, value)| format!("{:<8}{}", name, value))
+            .collect();
+        let title = format!(
+            "Survived {}, press any key to play again",
+            stats::format_time(player.data.stats.time)
         );
+        info!("Survival over: {:?}", lines);
+        self.ui.draw_messages(&title, &lines);
+        self.ui.refresh();
+        self.wait_key();
+        self.new_game();
+    }
+
+    // Every puzzle file on the boot volume, by file name
+    fn load_puzzles(&mut self) -> Puzzles {
+        let mut list = Vec::new();
+        let mut errors = Vec::new();
+        if let Some(storage) = self.storage.as_mut() {
+            let mut files = storage.list_files(PUZZLE_DIR);
+            files.sort();
+            for file in files {
+                let name = file
+                    .rsplit_once('.')
+                    .map_or(file.as_str(), |(name, _)| name);
+                let result = match storage.read(&format!("{}/{}", PUZZLE_DIR, file)) {
+                    Some(src) => Puzzle::parse(name, &String::from_utf8_lossy(&src)),
+                    None => Err(String::from("could not be read")),
+                };
+                match result {
+                    Ok(puzzle) => list.push(puzzle),
+                    Err(e) => {
+                        warn!("Puzzle {}: {}", file, e);
+                        errors.push(format!("{}: {}", file, e));
+                    }
+                }
+            }
+        }
+        Puzzles {
+            list,
+            errors,
+            selected: 0,
+            outcome: None,
+        }
+    }
+
+    fn step_puzzle_list(&mut self) {
+        let key = match self.read_key() {
+            Some(key) => key,
+            None => return,
+        };
+        let puzzles = self.puzzles.as_mut().unwrap();
+        let count = puzzles.list.len();
+        match key {
+            KeyCode::Escape => return self.open_settings(),
+            KeyCode::Up if count > 0 => puzzles.selected = (puzzles.selected + count - 1) % count,
+            KeyCode::Down if count > 0 => puzzles.selected = (puzzles.selected + 1) % count,
+            KeyCode::Char('\r') if count > 0 => return self.start_puzzle(),
+            _ => return,
+        }
+        self.draw_puzzle_list();
+        self.ui.refresh();
+    }
 
-        tetrimino.place(
-            &mut self.data,
-            (active_mino.1, active_mino.2),
-            active_mino.3,
-            true,
-            false,
+    fn draw_puzzle_list(&mut self) {
+        let puzzles = self.puzzles.as_ref().unwrap();
+        if puzzles.list.is_empty() {
+            let title = format!("No puzzles in {}, Esc: settings", PUZZLE_DIR);
+            self.ui.draw_messages(&title, &puzzles.errors);
+            return;
+        }
+        let items: Vec<(String, String)> = puzzles
+            .list
+            .iter()
+            .map(|puzzle| (puzzle.name.clone(), puzzle.goal.describe()))
+            .collect();
+        self.ui.draw_menu(
+            "Puzzles",
+            &items,
+            puzzles.selected,
+            "Enter: play  Esc: settings",
         );
+    }
 
-        let res = tetrimino.place(&mut self.data, (x, y), rot, false, true);
+    // The selected puzzle from the start
+    fn start_puzzle(&mut self) {
+        let puzzles = self.puzzles.as_mut().unwrap();
+        puzzles.outcome = None;
+        let data = puzzles.list[puzzles.selected].game();
+        let player = Player::with_data(data, 0, 0, &self.settings);
+        self.versus = Some(Versus::with_players(vec![player]));
+        self.ui.draw_background();
+        self.draw_versus();
+        self.ui.refresh();
+        self.last_tick = CLOCK.load(Ordering::Relaxed);
+    }
 
-        if res {
-            tetrimino.place(
-                &mut self.data,
-                (active_mino.1, active_mino.2),
-                active_mino.3,
-                false,
-                false,
-            );
+    // Retry, go on to the next puzzle or back to the list
+    fn show_puzzle_results(&mut self, outcome: Outcome) {
+        let puzzles = self.puzzles.as_ref().unwrap();
+        let puzzle = &puzzles.list[puzzles.selected];
+        let title = match outcome {
+            Outcome::Solved => "Solved, Enter: next  R: retry  Esc: list",
+            Outcome::Failed => "Failed, R: retry  Esc: list",
+        };
+        info!("Puzzle {}: {:?}", puzzle.name, outcome);
+        let lines = [puzzle.name.clone(), puzzle.goal.describe()];
+        self.ui.draw_messages(title, &lines);
+        self.ui.refresh();
 
-            if dpos == (0, -1) {
-                self.data.hold_flag = false;
-                return true;
+        let key = self.wait_key();
+        let puzzles = self.puzzles.as_mut().unwrap();
+        match key {
+            KeyCode::Char('r') => self.start_puzzle(),
+            KeyCode::Char('\r') if outcome == Outcome::Solved => {
+                puzzles.selected = (puzzles.selected + 1) % puzzles.list.len();
+                self.start_puzzle();
             }
-        } else {
-            if self.waiting_lockdown {
-                self.manage_lockdown_timer(true);
+            _ => {
+                self.versus = None;
+                self.ui.draw_background();
+                self.draw_puzzle_list();
+                self.ui.refresh();
+            }
+        }
+    }
+
+    fn read_key(&mut self) -> Option<KeyCode> {
+        if let Some(key) = self.serial.as_mut().and_then(|serial| serial.read_key()) {
+            return Some(key);
+        }
+        match self.keyboard.read_key().unwrap().unwrap()? {
+            Key::Special(ScanCode::UP) => Some(KeyCode::Up),
+            Key::Special(ScanCode::DOWN) => Some(KeyCode::Down),
+            Key::Special(ScanCode::LEFT) => Some(KeyCode::Left),
+            Key::Special(ScanCode::RIGHT) => Some(KeyCode::Right),
+            Key::Special(ScanCode::ESCAPE) => Some(KeyCode::Escape),
+            Key::Printable(c) => Some(KeyCode::Char(c.into())),
+            _ => None,
+        }
+    }
+
+    fn wait_key(&mut self) -> KeyCode {
+        loop {
+            // The serial port has no event to wait for
+            if self.serial.is_some() {
+                if let Some(key) = self.read_key() {
+                    return key;
+                }
+                continue;
+            }
+            unsafe {
+                system_table()
+                    .as_ref()
+                    .boot_services()
+                    .wait_for_event(&mut [self.keyboard.wait_for_key_event()])
+                    .unwrap()
+                    .unwrap();
             }
-            self.move_ghost(true);
-            self.data.active_mino = (tetrimino, x, y, rot);
-            // Test if tetrimino has hit ground
-            if tetrimino.place(&mut self.data, (x, y - 1), rot, false, true)
-                && !self.waiting_lockdown
-            {
-                self.manage_lockdown_timer(false);
+            if let Some(key) = self.read_key() {
+                return key;
             }
-            tetrimino.place(&mut self.data, (x, y), rot, false, false);
-            self.move_ghost(false);
         }
-        if dpos == (0, -1) {
-            DROP_FLAG.store(false, Ordering::Relaxed);
-            self.start_drop_timer();
+    }
+
+    fn open_settings(&mut self) {
+        let current_mode = self
+            .ui
+            .video()
+            .and_then(|video| video.current_mode())
+            .unwrap_or(0);
+        let mut skins = vec![
+            String::from(settings::DEFAULT_SKIN),
+            String::from(settings::FLAT_SKIN),
+        ];
+        if let Some(storage) = self.storage.as_mut() {
+            skins.extend(storage.list_dirs(SKIN_DIR));
         }
+        self.menu = Some(Menu::open(&self.settings, current_mode, skins));
+        self.player.data.state = GameState::Settings;
+        self.draw_menu();
+    }
 
-        false
+    fn handle_menu_event(&mut self, event: MenuEvent) {
+        match event {
+            MenuEvent::None => {}
+            MenuEvent::Redraw => {
+                if self.apply_display() {
+                    self.ui.draw_background();
+                }
+                self.draw_menu();
+            }
+            MenuEvent::Save => {
+                self.save_settings();
+                self.close_settings();
+            }
+            MenuEvent::Close => self.close_settings(),
+            MenuEvent::PreviewMode(mode) => {
+                self.previous_mode = self.ui.video().and_then(|video| video.current_mode());
+                self.set_video_mode(mode);
+                self.set_revert_timer(false);
+                self.draw_menu();
+            }
+            MenuEvent::KeepMode => {
+                self.set_revert_timer(true);
+                let mode = self.menu.as_ref().unwrap().mode;
+                let (width, height) = match self.ui.video() {
+                    Some(video) => video.modes()[mode].resolution,
+                    None => return,
+                };
+                self.menu
+                    .as_mut()
+                    .unwrap()
+                    .keep_mode(&mut self.settings, (width as u32, height as u32));
+                self.save_settings();
+                self.draw_menu();
+            }
+            MenuEvent::Benchmark => {
+                let (blt, linear) = match self.ui.video() {
+                    Some(video) => video.benchmark(BENCHMARK_FRAMES),
+                    None => return,
+                };
+                let lines = [
+                    format!("blt:    {} cycles/frame", blt),
+                    match linear {
+                        Some(linear) => format!("linear: {} cycles/frame", linear),
+                        None => String::from("linear: unsupported by this mode"),
+                    },
+                ];
+                info!("Benchmark: {} / {}", lines[0], lines[1]);
+                self.ui.draw_messages("Benchmark, press any key", &lines);
+                self.ui.refresh();
+                self.wait_key();
+                self.ui.draw_background();
+                self.draw_menu();
+            }
+            MenuEvent::RevertMode => {
+                self.set_revert_timer(true);
+                if let Some(mode) = self.previous_mode.take() {
+                    self.set_video_mode(mode);
+                }
+                self.draw_menu();
+            }
+            MenuEvent::Export => {
+                self.export_board();
+                self.ui.draw_background();
+                self.draw_menu();
+            }
+        }
     }
 
-    fn start_drop_timer(&self) {
-        unsafe {
-            system_table()
-                .as_ref()
-                .boot_services()
-                .set_timer(self.drop_event, TimerTrigger::Relative(FALL_SPEED * 10000))
-                .unwrap()
-                .unwrap();
+    // The board of player 1 as a fumen, shown and written to EXPORT_FILE
+    fn export_board(&mut self) {
+        let data = if let Some(versus) = self.versus.as_ref() {
+            Some(&versus.players[0].data)
+        } else if let Some(online) = self.online.as_ref() {
+            online.player.as_ref().map(|player| &player.data)
+        } else if self.puzzles.is_some() {
+            None
+        } else {
+            Some(&self.player.data)
+        };
+        let fumen = match data {
+            Some(data) => fumen::encode(&[fumen::Page::from_game(data, self.settings.previews)]),
+            None => {
+                self.ui
+                    .draw_messages("No board to export, press any key", &[]);
+                self.ui.refresh();
+                self.wait_key();
+                return;
+            }
+        };
+        info!("Board: {}", fumen);
+
+        let saved = match self.storage.as_mut() {
+            Some(storage) => storage.write(EXPORT_FILE, fumen.as_bytes()),
+            None => false,
+        };
+        let title = if saved {
+            format!("Board written to {}, press any key", EXPORT_FILE)
+        } else {
+            warn!("Failed to write {}", EXPORT_FILE);
+            format!("Could not write {}, press any key", EXPORT_FILE)
+        };
+        let lines: Vec<String> = fumen
+            .as_bytes()
+            .chunks(EXPORT_LINE)
+            .map(|line| String::from_utf8_lossy(line).into_owned())
+            .collect();
+        self.ui.draw_messages(&title, &lines);
+        self.ui.refresh();
+        self.wait_key();
+    }
+
+    // Puts the renderer, layout and skin from the settings into effect,
+    // returns whether the background has to be redrawn
+    fn apply_display(&mut self) -> bool {
+        if let Some(video) = self.ui.video() {
+            video.set_renderer(self.settings.renderer);
+        }
+        let resized = self.ui.set_layout(&self.settings, self.players());
+        if self.settings.skin == self.loaded_skin {
+            return resized;
+        }
+        if let Err(e) = self.load_skin() {
+            self.ui
+                .draw_messages("Could not load skin, press any key", &[e]);
+            self.ui.refresh();
+            self.wait_key();
+        }
+        true
+    }
+
+    fn set_video_mode(&mut self, mode: usize) {
+        if let Some(video) = self.ui.video() {
+            video.set_mode(mode);
+        }
+        self.ui.set_layout(&self.settings, self.players());
+        self.ui.draw_background();
+    }
+
+    // Falls back to the built-in skin if the selected one can't be loaded
+    fn load_skin(&mut self) -> Result<(), String> {
+        let name = self.settings.skin.clone();
+        self.loaded_skin = name.clone();
+        if name == settings::DEFAULT_SKIN {
+            self.ui.set_skin(Skin::embedded());
+            return Ok(());
+        }
+        if name == settings::FLAT_SKIN {
+            self.ui.set_skin(Skin::flat());
+            return Ok(());
+        }
+
+        let result = match self.storage.as_mut() {
+            Some(storage) => {
+                let manifest = storage.read(&format!("{}/{}/skin.cfg", SKIN_DIR, name));
+                let sheet = storage.read(&format!("{}/{}/sheet.bmp", SKIN_DIR, name));
+                match (manifest, sheet) {
+                    (Some(manifest), Some(sheet)) => {
+                        Skin::from_sheet(&String::from_utf8_lossy(&manifest), &sheet)
+                    }
+                    _ => Err(String::from("missing skin.cfg or sheet.bmp")),
+                }
+            }
+            None => Err(String::from("boot volume is not available")),
+        };
+
+        match result {
+            Ok(skin) => {
+                self.ui.set_skin(skin);
+                Ok(())
+            }
+            Err(e) => {
+                warn!("Skin {}: {}", name, e);
+                self.ui.set_skin(Skin::embedded());
+                Err(format!("skin `{}`: {}", name, e))
+            }
+        }
+    }
+
+    fn save_settings(&mut self) {
+        let saved = match self.storage.as_mut() {
+            Some(storage) => {
+                storage.write(settings::FILE_NAME, self.settings.serialize().as_bytes())
+            }
+            None => false,
+        };
+        if !saved {
+            warn!("Failed to write {}", settings::FILE_NAME);
+        }
+    }
+
+    fn draw_menu(&mut self) {
+        let menu = self.menu.as_ref().unwrap();
+        match menu.page {
+            Page::Settings => {
+                let mut items = self.settings.items();
+                if menu.rebinding {
+                    items[menu.selected].1 = String::from("<press a key>");
+                }
+                self.ui.draw_menu(
+                    "Settings",
+                    &items,
+                    menu.selected,
+                    "Enter: select  S: save  X: export  Esc: cancel",
+                );
+            }
+            Page::VideoModes => {
+                let (modes, current) = match self.ui.video() {
+                    Some(video) => (video.modes(), video.current_mode()),
+                    None => (Vec::new(), None),
+                };
+                let items: Vec<(String, String)> = modes
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, mode)| {
+                        let (width, height) = mode.resolution;
+                        let marker = if Some(idx) == current { " *" } else { "" };
+                        (
+                            format!("{}x{}{}", width, height, marker),
+                            String::from(mode.format),
+                        )
+                    })
+                    .collect();
+                self.ui.draw_menu(
+                    "Video mode",
+                    &items,
+                    menu.mode,
+                    "Enter: try mode  B: benchmark  Esc: back",
+                );
+            }
+            Page::ConfirmMode => {
+                let lines = [format!(
+                    "Reverting in {} seconds unless confirmed.",
+                    MODE_REVERT_TIMEOUT / 10_000_000
+                )];
+                self.ui
+                    .draw_messages("Keep this mode? Enter: keep  Esc: revert", &lines);
+            }
         }
+        self.ui.refresh();
     }
 
-    fn manage_lockdown_timer(&mut self, cancel: bool) {
-        self.waiting_lockdown = !cancel;
+    fn set_revert_timer(&mut self, cancel: bool) {
+        REVERT_FLAG.store(false, Ordering::Relaxed);
         unsafe {
             system_table()
                 .as_ref()
                 .boot_services()
                 .set_timer(
-                    self.lockdown_event,
+                    self.revert_event,
                     if cancel {
                         TimerTrigger::Cancel
                     } else {
-                        TimerTrigger::Relative(5000000)
+                        TimerTrigger::Relative(MODE_REVERT_TIMEOUT)
                     },
                 )
                 .unwrap()
                 .unwrap();
         }
     }
-}
-
-pub fn tick_piece(_: Event) {
-    DROP_FLAG.store(true, Ordering::Relaxed);
-}
-pub fn tick_lockdown(_: Event) {
-    LOCKDOWN_FLAG.store(true, Ordering::Relaxed);
-}
-
-impl Tetrimino {
-    pub fn spawn(self, mut data: &mut GameData) -> bool {
-        let pos = (7, 25);
-        let res = self.place(&mut data, pos, 0, false, true);
-        self.place(&mut data, pos, 0, false, false);
-        data.active_mino = (self, pos.0, pos.1, 0);
-        res
-    }
-
-    pub fn rotate(
-        self,
-        mut data: &mut GameData,
-        pos: (usize, usize),
-        old_rot: u8,
-        new_rot: u8,
-    ) -> bool {
-        let mut tests: Vec<(isize, isize)> = vec![(0, 0)];
-
-        if data.active_mino.0 != Tetrimino::I {
-            for i in 0..4 {
-                tests.push(match (old_rot, new_rot) {
-                    (0, 1) | (2, 1) => match i {
-                        0 => (-1, 0),
-                        1 => (-1, 1),
-                        2 => (0, -2),
-                        3 => (-1, -2),
-                        _ => unreachable!(),
-                    },
-                    (1, 0) | (1, 2) => match i {
-                        0 => (1, 0),
-                        1 => (1, -1),
-                        2 => (0, 2),
-                        3 => (1, 2),
-                        _ => unreachable!(),
-                    },
-                    (2, 3) | (0, 3) => match i {
-                        0 => (1, 0),
-                        1 => (1, 1),
-                        2 => (0, -2),
-                        3 => (1, -2),
-                        _ => unreachable!(),
-                    },
-                    (3, 2) | (3, 0) => match i {
-                        0 => (-1, 0),
-                        1 => (-1, -1),
-                        2 => (0, 2),
-                        3 => (-1, 2),
-                        _ => unreachable!(),
-                    },
 
-                    _ => unreachable!(),
-                });
-            }
-        } else {
-            for i in 0..4 {
-                tests.push(match (old_rot, new_rot) {
-                    (0, 1) | (3, 2) => match i {
-                        0 => (-2, 0),
-                        1 => (1, 0),
-                        2 => (-2, 1),
-                        3 => (1, 2),
-                        _ => unreachable!(),
-                    },
-                    (1, 0) | (2, 3) => match i {
-                        0 => (2, 0),
-                        1 => (-1, 0),
-                        2 => (2, 1),
-                        3 => (-1, -2),
-                        _ => unreachable!(),
-                    },
-                    (1, 2) | (0, 3) => match i {
-                        0 => (-1, 0),
-                        1 => (2, 0),
-                        2 => (-1, 2),
-                        3 => (2, -1),
-                        _ => unreachable!(),
-                    },
-                    (2, 1) | (3, 0) => match i {
-                        0 => (1, 0),
-                        1 => (-2, 0),
-                        2 => (1, -2),
-                        3 => (-2, 1),
-                        _ => unreachable!(),
-                    },
-
-                    _ => unreachable!(),
-                });
-            }
-        }
-
-        self.place(&mut data, pos, old_rot, true, false);
-        for (off_x, off_y) in tests.iter() {
-            let (x, y) = (
-                (pos.0 as isize + off_x) as usize,
-                (pos.1 as isize + off_y) as usize,
-            );
-            if !self.place(&mut data, (x, y), new_rot, false, true) {
-                self.place(&mut data, (x, y), new_rot, false, false);
-                data.active_mino.1 = x;
-                data.active_mino.2 = y;
-                data.active_mino.3 = new_rot;
-                return true;
-            }
-        }
-        self.place(&mut data, pos, old_rot, false, false);
-        false
-    }
-
-    fn matrix_draw(
-        self,
-        data: &mut GameData,
-        pos: (usize, usize),
-        test: bool,
-        delete: bool,
-    ) -> bool {
-        let mino = data.matrix[pos.1][pos.0].borrow_mut();
-        if test {
-            !(4..14).contains(&pos.0)
-                || !(4..44).contains(&pos.1)
-                || (mino.is_some() && *mino != Some(Tetrimino::Ghost))
-        } else {
-            if self != Tetrimino::Ghost || !mino.is_some() || *mino == Some(Tetrimino::Ghost) {
-                *mino = if delete { None } else { Some(self) };
-            }
-            false
+    fn close_settings(&mut self) {
+        self.menu = None;
+        // Escape puts back the settings from before the menu opened
+        self.apply_display();
+        // Switching modes, opponents or garbage rules starts a new game,
+        // network games can only be paused while searching
+        let cpu = self.bot.as_ref().map(|bot| bot.difficulty());
+        let opponent = self.mode() == Mode::Versus && self.settings.cpu != cpu;
+        let rules = self
+            .versus
+            .as_ref()
+            .map(|versus| versus.players[0].garbage.rules());
+        let garbage = rules.map_or(false, |rules| rules != self.settings.garbage);
+        if self.settings.mode != self.mode() || opponent || garbage || self.online.is_some() {
+            self.new_game();
+            return;
+        }
+        // The timers run off the game clock, which stood still while the menu was open
+        self.player.data.state = GameState::Drop;
+        if self.puzzles.is_some() && self.versus.is_none() {
+            self.ui.draw_background();
+            self.draw_puzzle_list();
+            self.ui.refresh();
+            return;
+        }
+        if self.versus.is_some() {
+            self.ui.draw_background();
+            self.draw_versus();
+            self.ui.refresh();
+            return;
         }
+        self.ui.draw_background();
+        draw_player(&mut *self.ui, 0, &self.player);
+        self.ui.refresh();
     }
+}
 
-    // pos is top-left corner of: https://tetris.fandom.com/wiki/SRS?file=SRS-pieces.png
-    pub fn place(
-        self,
-        mut data: &mut GameData,
-        pos: (usize, usize),
-        rot: u8,
-        delete: bool,
-        test: bool,
-    ) -> bool {
-        let rot = rot % 4;
-
-        let mino = if self != Tetrimino::Ghost {
-            self
-        } else {
-            data.active_mino.0
-        };
-
-        let rot_data = get_rotation(mino);
-
-        let block = ((rot_data & (0xFFFF000000000000 >> (rot * 16))) >> (48 - rot * 16)) as u16;
-        for y in 0..4 {
-            let row = (block & (0xF000 >> (y * 4))) >> (12 - y * 4);
-            for x in 0..4 {
-                if (row >> (3 - x)) % 2 == 1
-                    && self.matrix_draw(&mut data, (pos.0 + x, pos.1 - y), test, delete)
-                {
-                    return true;
-                }
-            }
+// What an input changed on a board
+fn draw_input<F: Frontend + ?Sized>(ui: &mut F, idx: usize, player: &Player, input: Input) {
+    let data = &player.data;
+    match input {
+        Input::Lock => {
+            ui.draw_hold(idx, &data.hold, data.hold_flag);
+            ui.draw_queue(idx, &data.queue);
+            ui.draw_stats(idx, &data.stats);
+            ui.draw_garbage(idx, player.garbage.pending());
         }
-
-        false
+        Input::Action(Action::Hold) => {
+            ui.draw_hold(idx, &data.hold, data.hold_flag);
+            ui.draw_queue(idx, &data.queue);
+        }
+        _ => {}
     }
+    ui.draw_board(idx, data);
+}
+
+fn draw_player<F: Frontend + ?Sized>(ui: &mut F, idx: usize, player: &Player) {
+    let data = &player.data;
+    ui.draw_board(idx, data);
+    ui.draw_hold(idx, &data.hold, data.hold_flag);
+    ui.draw_queue(idx, &data.queue);
+    ui.draw_stats(idx, &data.stats);
+    ui.draw_timer(idx, data.stats.time);
+    ui.draw_garbage(idx, player.garbage.pending());
 }
 
-/*
-All 4 rotations of a piece encoded as a u64.
-// TODO explain this lol
- */
-pub const ROTATIONS: [u64; 7] = [
-    0x6600660066006600, // O
-    0x0F00222200F04444, // I
-    0x4E0046400E404C40, // T
-    0x2E0044600E80C440, // L
-    0x8E0064400E2044C0, // J
-    0x6C00462006C08C40, // S
-    0xC60026400C604C80, // Z
-];
-
-pub fn get_rotation(tetrimino: Tetrimino) -> &'static u64 {
-    &ROTATIONS[match tetrimino {
-        Tetrimino::O => 0,
-        Tetrimino::I => 1,
-        Tetrimino::T => 2,
-        Tetrimino::L => 3,
-        Tetrimino::J => 4,
-        Tetrimino::S => 5,
-        Tetrimino::Z => 6,
-        _ => unreachable!(),
-    }]
+pub fn tick_revert(_: Event) {
+    REVERT_FLAG.store(true, Ordering::Relaxed);
+}
+pub fn tick_clock(_: Event) {
+    CLOCK.fetch_add(CLOCK_INTERVAL, Ordering::Relaxed);
 }
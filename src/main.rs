@@ -3,20 +3,19 @@
 #![feature(abi_efiapi)]
 extern crate alloc;
 
-#[macro_use]
-extern crate lazy_static;
-
+mod console;
 mod framebuffer;
+mod fs;
 mod game;
-mod sprites;
-mod ui;
+mod net;
+mod serial;
 
 use log::info;
 use uefi::prelude::*;
 use uefi_services::init;
 
 #[entry]
-fn efi_main(_image: Handle, sys_table: SystemTable<Boot>) -> Status {
+fn efi_main(image: Handle, sys_table: SystemTable<Boot>) -> Status {
     init(&sys_table).unwrap().unwrap();
 
     // Disable the watchdog timer
@@ -28,7 +27,7 @@ fn efi_main(_image: Handle, sys_table: SystemTable<Boot>) -> Status {
 
     info!("Hello, world!");
 
-    let mut game = game::Rustris::new();
+    let mut game = game::Rustris::new(image);
     info!("Starting game...");
     game.start();
 }
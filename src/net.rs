@@ -0,0 +1,200 @@
+use core::ptr;
+use rustris_engine::net::{Link, MESSAGE_SIZE};
+use uefi::proto::Protocol;
+use uefi::{unsafe_guid, Event, Status};
+use uefi_services::system_table;
+
+// Local experimental EtherType, nothing else on the network should use it
+const ETHER_TYPE: u16 = 0x88b5;
+const HEADER_SIZE: usize = 14;
+// Shorter frames get padded by some cards and dropped by others
+const MIN_FRAME: usize = 60;
+// Transmit polls to wait for a buffer to come back
+const TRANSMIT_POLLS: usize = 10_000;
+
+const STATE_STOPPED: u32 = 0;
+const STATE_STARTED: u32 = 1;
+const RECEIVE_UNICAST: u32 = 0x01;
+const RECEIVE_BROADCAST: u32 = 0x04;
+
+type MacAddress = [u8; 32];
+
+#[allow(dead_code)]
+#[repr(C)]
+struct NetworkMode {
+    state: u32,
+    hw_address_size: u32,
+    media_header_size: u32,
+    max_packet_size: u32,
+    nv_ram_size: u32,
+    nv_ram_access_size: u32,
+    receive_filter_mask: u32,
+    receive_filter_setting: u32,
+    max_mcast_filter_count: u32,
+    mcast_filter_count: u32,
+    mcast_filter: [MacAddress; 16],
+    current_address: MacAddress,
+    broadcast_address: MacAddress,
+    permanent_address: MacAddress,
+    if_type: u8,
+    mac_address_changeable: bool,
+    multiple_tx_supported: bool,
+    media_present_supported: bool,
+    media_present: bool,
+}
+
+// Not wrapped by the uefi crate, only the calls used here have real signatures
+#[allow(dead_code)]
+#[repr(C)]
+#[unsafe_guid("a19832b9-ac25-11d3-9a2d-0090273fc14d")]
+#[derive(Protocol)]
+struct SimpleNetwork {
+    revision: u64,
+    start: extern "efiapi" fn(this: &mut SimpleNetwork) -> Status,
+    stop: usize,
+    initialize: extern "efiapi" fn(
+        this: &mut SimpleNetwork,
+        extra_rx_buffer_size: usize,
+        extra_tx_buffer_size: usize,
+    ) -> Status,
+    reset: usize,
+    shutdown: usize,
+    receive_filters: extern "efiapi" fn(
+        this: &mut SimpleNetwork,
+        enable: u32,
+        disable: u32,
+        reset_mcast_filter: bool,
+        mcast_filter_count: usize,
+        mcast_filter: *const MacAddress,
+    ) -> Status,
+    station_address: usize,
+    statistics: usize,
+    mcast_ip_to_mac: usize,
+    nv_data: usize,
+    get_status: extern "efiapi" fn(
+        this: &mut SimpleNetwork,
+        interrupt_status: *mut u32,
+        tx_buffer: *mut *mut u8,
+    ) -> Status,
+    transmit: extern "efiapi" fn(
+        this: &mut SimpleNetwork,
+        header_size: usize,
+        buffer_size: usize,
+        buffer: *const u8,
+        src_address: *const MacAddress,
+        dest_address: *const MacAddress,
+        protocol: *const u16,
+    ) -> Status,
+    receive: extern "efiapi" fn(
+        this: &mut SimpleNetwork,
+        header_size: *mut usize,
+        buffer_size: *mut usize,
+        buffer: *mut u8,
+        src_address: *mut MacAddress,
+        dest_address: *mut MacAddress,
+        protocol: *mut u16,
+    ) -> Status,
+    wait_for_packet: Event,
+    mode: *const NetworkMode,
+}
+
+// Raw Ethernet frames on the first network card, every message goes out as a broadcast
+pub struct Network<'a> {
+    snp: &'a mut SimpleNetwork,
+    frame: [u8; MIN_FRAME],
+}
+
+impl Network<'_> {
+    pub fn open() -> Option<Self> {
+        let protocol = unsafe {
+            system_table()
+                .as_ref()
+                .boot_services()
+                .locate_protocol::<SimpleNetwork>()
+                .ok()?
+                .unwrap()
+        };
+
+        let snp = unsafe { &mut *protocol.get() };
+        if snp.mode().state == STATE_STOPPED && (snp.start)(snp).is_error() {
+            return None;
+        }
+        if snp.mode().state == STATE_STARTED && (snp.initialize)(snp, 0, 0).is_error() {
+            return None;
+        }
+        let filters = RECEIVE_UNICAST | RECEIVE_BROADCAST;
+        (snp.receive_filters)(snp, filters, 0, false, 0, ptr::null());
+        Some(Self {
+            snp,
+            frame: [0; MIN_FRAME],
+        })
+    }
+
+    // The last bytes of the MAC address, unique enough to tell the machines apart
+    pub fn id(&self) -> u32 {
+        let mac = &self.snp.mode().current_address;
+        u32::from_be_bytes([mac[2], mac[3], mac[4], mac[5]])
+    }
+}
+
+impl SimpleNetwork {
+    fn mode(&self) -> &NetworkMode {
+        unsafe { &*self.mode }
+    }
+}
+
+impl Link for Network<'_> {
+    fn send(&mut self, data: &[u8]) {
+        let len = (HEADER_SIZE + data.len()).min(MIN_FRAME);
+        self.frame[HEADER_SIZE..len].copy_from_slice(&data[..len - HEADER_SIZE]);
+        let broadcast = self.snp.mode().broadcast_address;
+        let status = (self.snp.transmit)(
+            self.snp,
+            HEADER_SIZE,
+            MIN_FRAME,
+            self.frame.as_ptr(),
+            ptr::null(),
+            &broadcast,
+            &ETHER_TYPE,
+        );
+        if status.is_error() {
+            return;
+        }
+        // The frame buffer is reused, so wait until the card is done with it
+        for _ in 0..TRANSMIT_POLLS {
+            let mut tx_buffer = ptr::null_mut();
+            let status = (self.snp.get_status)(self.snp, ptr::null_mut(), &mut tx_buffer);
+            if status.is_error() || !tx_buffer.is_null() {
+                break;
+            }
+        }
+    }
+
+    fn recv(&mut self, buffer: &mut [u8]) -> Option<usize> {
+        let mut frame = [0u8; 1536];
+        loop {
+            let mut header_size = 0;
+            let mut size = frame.len();
+            let mut protocol = 0;
+            let status = (self.snp.receive)(
+                self.snp,
+                &mut header_size,
+                &mut size,
+                frame.as_mut_ptr(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &mut protocol,
+            );
+            // Not ready once nothing is left
+            if status.is_error() {
+                return None;
+            }
+            if protocol != ETHER_TYPE || size < header_size + MESSAGE_SIZE {
+                continue;
+            }
+            let len = (size - header_size).min(buffer.len());
+            buffer[..len].copy_from_slice(&frame[header_size..header_size + len]);
+            return Some(len);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use rustris_engine::ansi::Decoder;
+use rustris_engine::settings::KeyCode;
+use uefi::proto::console::serial::Serial;
+use uefi_services::system_table;
+
+// Read timeout in µs, the default of one second would stall the game loop
+const READ_TIMEOUT: u32 = 1000;
+
+// Set once the port is handed out, another `open` would alias it
+static OPENED: AtomicBool = AtomicBool::new(false);
+
+// The first serial port, as ANSI output and as a source of key presses
+pub struct SerialPort<'a> {
+    serial: Rc<RefCell<&'a mut Serial<'a>>>,
+    decoder: Decoder,
+}
+
+impl<'a> SerialPort<'a> {
+    // Only the first call hands out the port, `share` gives out more handles to it
+    pub fn open() -> Option<Self> {
+        if OPENED.load(Ordering::SeqCst) {
+            return None;
+        }
+        let protocol = unsafe {
+            system_table()
+                .as_ref()
+                .boot_services()
+                .locate_protocol::<Serial>()
+                .ok()?
+                .unwrap()
+        };
+
+        let serial = unsafe { &mut *protocol.get() };
+        let mut mode = *serial.io_mode();
+        mode.timeout = READ_TIMEOUT;
+        serial.set_attributes(&mode).ok()?;
+        OPENED.store(true, Ordering::SeqCst);
+        Some(Self {
+            serial: Rc::new(RefCell::new(serial)),
+            decoder: Decoder::default(),
+        })
+    }
+
+    // The same port, the frontend writes to one handle and the game reads from another
+    pub fn share(&self) -> Self {
+        Self {
+            serial: self.serial.clone(),
+            decoder: Decoder::default(),
+        }
+    }
+
+    pub fn read_key(&mut self) -> Option<KeyCode> {
+        let mut byte = [0];
+        // Times out once nothing is left to read
+        while self.serial.borrow_mut().read(&mut byte).is_ok() {
+            if let Some(key) = self.decoder.feed(byte[0]) {
+                return Some(key);
+            }
+        }
+        self.decoder.idle()
+    }
+}
+
+impl fmt::Write for SerialPort<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self.serial.borrow_mut().write(s.as_bytes()) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(fmt::Error),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use rustris_engine::tbp::{self, BotMessage};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+// A bot engine speaking the Tetris Bot Protocol on its stdin and stdout
+pub struct External {
+    child: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    pub name: String, // with version and author
+}
+
+impl External {
+    // Runs `command` and waits until the bot is ready
+    pub fn spawn(command: &str) -> Result<Self, String> {
+        let mut words = command.split_whitespace();
+        let program = words.next().ok_or("empty bot command")?;
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            // Anything the bot logs would end up on top of the game
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("{}: {}", program, e))?;
+        let input = child.stdin.take().unwrap();
+        let output = BufReader::new(child.stdout.take().unwrap());
+        let mut bot = Self {
+            child,
+            input,
+            output,
+            name: String::new(),
+        };
+
+        match bot.recv()? {
+            BotMessage::Info {
+                name,
+                version,
+                author,
+            } => bot.name = format!("{} {} by {}", name, version, author),
+            other => return Err(format!("expected info, got {:?}", other)),
+        }
+        bot.send(&tbp::rules())?;
+        match bot.recv()? {
+            BotMessage::Ready => Ok(bot),
+            BotMessage::Error(reason) => Err(format!("bot rejected the rules: {}", reason)),
+            other => Err(format!("expected ready, got {:?}", other)),
+        }
+    }
+
+    pub fn send(&mut self, message: &str) -> Result<(), String> {
+        writeln!(self.input, "{}", message)
+            .and_then(|_| self.input.flush())
+            .map_err(|e| format!("bot: {}", e))
+    }
+
+    pub fn recv(&mut self) -> Result<BotMessage, String> {
+        let mut line = String::new();
+        match self.output.read_line(&mut line) {
+            Ok(0) => Err(String::from("bot exited")),
+            Ok(_) => tbp::parse(line.trim()).map_err(|e| format!("bot: {}", e)),
+            Err(e) => Err(format!("bot: {}", e)),
+        }
+    }
+}
+
+impl Drop for External {
+    fn drop(&mut self) {
+        let _ = self.send(&tbp::quit());
+        let _ = self.child.wait();
+    }
+}
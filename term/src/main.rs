@@ -0,0 +1,397 @@
+mod bot;
+mod raw;
+
+use bot::External;
+use raw::RawMode;
+use rustris_engine::ansi::{self, AnsiTerminal, Decoder};
+use rustris_engine::effects::{self, Effect, Effects};
+use rustris_engine::frontend::Frontend;
+use rustris_engine::fumen::{self, Page};
+use rustris_engine::game::{Clear, GameData, GameState};
+use rustris_engine::player::Player;
+use rustris_engine::puzzle::{Outcome, Puzzle};
+use rustris_engine::replay::{Input, Replay};
+use rustris_engine::settings::{self, Action, KeyCode, Settings};
+use rustris_engine::tbp::{BotMessage, Feed};
+use rustris_engine::text::TextUi;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::{env, fs, process};
+
+// Longest wait for input in ms, keeps the timers and animations going
+const STEP: i32 = 10;
+// Time between the placements of an external bot in ms, so they can be followed
+const BOT_PACE: u64 = 200;
+
+struct Stdout(io::Stdout);
+
+impl fmt::Write for Stdout {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut out = self.0.lock();
+        out.write_all(s.as_bytes())
+            .and_then(|_| out.flush())
+            .map_err(|_| fmt::Error)
+    }
+}
+
+// Same rules as the firmware version, with the timers run off the wall clock
+struct Game {
+    player: Player,
+    ui: TextUi<AnsiTerminal<Stdout>>,
+    settings: Settings,
+    decoder: Decoder,
+    replay: Replay,
+    effects: Effects,
+    start: Instant,
+    puzzle: Option<Puzzle>,
+    outcome: Option<Outcome>, // once the puzzle is decided
+}
+
+impl Game {
+    // `data` is the board and pieces to start from, if not a new game
+    fn new(settings: Settings, seed: u64, data: Option<GameData>, puzzle: Option<Puzzle>) -> Self {
+        let size = raw::size().unwrap_or(ansi::SIZE);
+        let terminal = AnsiTerminal::new(Stdout(io::stdout()), size);
+        let player = match data {
+            Some(data) => Player::with_data(data, seed, 0, &settings),
+            None => Player::new(seed, seed, 0, &settings),
+        };
+        let mut game = Self {
+            player,
+            ui: TextUi::new(terminal, &settings, 1),
+            settings,
+            decoder: Decoder::default(),
+            replay: Replay::new(seed),
+            effects: Effects::default(),
+            start: Instant::now(),
+            puzzle,
+            outcome: None,
+        };
+        let data = &game.player.data;
+        game.ui.draw_hold(0, &data.hold, data.hold_flag);
+        game.ui.draw_queue(0, &data.queue);
+        game.ui.draw_stats(0, &data.stats);
+        game.draw();
+        game
+    }
+
+    fn draw(&mut self) {
+        self.ui.draw_board(0, &self.player.data);
+        self.ui
+            .draw_effects(0, &self.effects, self.player.data.stats.time);
+        self.ui.refresh();
+    }
+
+    // Advances the game time to now
+    fn tick(&mut self) {
+        let now = self.start.elapsed().as_millis() as u64;
+        let stats = &mut self.player.data.stats;
+        let shown = stats.time / 100;
+        stats.time = now;
+        if now / 100 != shown {
+            self.ui.draw_timer(0, now);
+        }
+        if !self.effects.is_empty() {
+            self.effects.update(now);
+            self.draw();
+        } else {
+            self.ui.refresh();
+        }
+    }
+
+    fn input(&mut self, input: Input) {
+        let now = self.player.data.stats.time;
+        self.replay.record(now, input);
+        let clear = self.player.input(input, now, &self.settings);
+        if let (Some(puzzle), Input::Lock) = (self.puzzle.as_ref(), input) {
+            self.outcome = puzzle.check(&self.player.data, clear.as_ref());
+        }
+
+        let data = &self.player.data;
+        match input {
+            Input::Lock => self.draw_lock(clear),
+            Input::Action(Action::Hold) => {
+                self.ui.draw_hold(0, &data.hold, data.hold_flag);
+                self.ui.draw_queue(0, &data.queue);
+            }
+            _ => {}
+        }
+        self.draw();
+    }
+
+    // Everything a locked piece changes besides the board
+    fn draw_lock(&mut self, clear: Option<Clear>) {
+        let data = &self.player.data;
+        let text = clear.map(|clear| clear.text()).unwrap_or_default();
+        if self.settings.animations && !text.is_empty() {
+            let now = data.stats.time;
+            self.effects.add(Effect::Popup(text), now, effects::POPUP);
+        }
+        self.ui.draw_hold(0, &data.hold, data.hold_flag);
+        self.ui.draw_queue(0, &data.queue);
+        self.ui.draw_stats(0, &data.stats);
+    }
+
+    fn play(&mut self) {
+        while self.player.data.state != GameState::Die && self.outcome.is_none() {
+            self.tick();
+            while let Some(input) = self.player.due(self.player.data.stats.time) {
+                self.input(input);
+            }
+            match raw::read_key(&mut self.decoder, STEP) {
+                Some(KeyCode::Escape) => break,
+                Some(key) => {
+                    if let Some(action) = self.settings.action(key) {
+                        self.player.data.stats.keys += 1;
+                        self.tick();
+                        self.input(Input::Action(action));
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+
+    // Plays the inputs back at their original pace, Escape stops early
+    fn watch(&mut self, replay: &Replay) {
+        for (time, input) in replay.inputs.iter() {
+            while self.player.data.stats.time < *time {
+                if raw::read_key(&mut self.decoder, STEP) == Some(KeyCode::Escape) {
+                    return;
+                }
+                self.tick();
+            }
+            let stats = &mut self.player.data.stats;
+            if let Input::Action(_) = input {
+                stats.keys += 1;
+            }
+            stats.time = *time;
+            self.input(*input);
+        }
+    }
+
+    // Lets an external bot place every piece, Escape stops early
+    fn autoplay(&mut self, bot: &mut External) -> Result<(), String> {
+        let mut feed = Feed::default();
+        bot.send(&feed.start(&self.player.data))?;
+        while self.player.data.state != GameState::Die {
+            bot.send(&rustris_engine::tbp::suggest())?;
+            let mv = match bot.recv()? {
+                BotMessage::Suggestion(moves) => *moves.first().ok_or("bot gave up")?,
+                other => return Err(format!("expected a suggestion, got {:?}", other)),
+            };
+            self.tick();
+            let (clear, messages) = feed.play(&mut self.player.data, &mv)?;
+            if let Some(clear) = &clear {
+                self.player.record_attack(clear);
+            }
+            self.draw_lock(clear);
+            self.draw();
+            if self.player.data.state == GameState::Die {
+                break;
+            }
+            for message in messages.iter() {
+                bot.send(message)?;
+            }
+
+            let next = self.player.data.stats.time + BOT_PACE;
+            while self.player.data.stats.time < next {
+                if raw::read_key(&mut self.decoder, STEP) == Some(KeyCode::Escape) {
+                    return Ok(());
+                }
+                self.tick();
+            }
+        }
+        Ok(())
+    }
+
+    fn show_results(&mut self, title: &str) {
+        let lines: Vec<String> = self
+            .player
+            .data
+            .stats
+            .rows()
+            .iter()
+            .map(|(name, value)| format!("{:<8}{}", name, value))
+            .collect();
+        self.ui.draw_messages(title, &lines);
+        self.ui.refresh();
+        raw::read_key(&mut self.decoder, -1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: term [--config FILE] [--record FILE] [--replay FILE] [--bot COMMAND] [--puzzle FILE|FUMEN] [--board FILE|FUMEN] [--export FILE]"
+    );
+    process::exit(2);
+}
+
+fn main() {
+    let mut config = PathBuf::from(settings::FILE_NAME);
+    let mut record = None;
+    let mut watch = None;
+    let mut command = None;
+    let mut puzzle = None;
+    let mut board = None;
+    let mut export = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().map(PathBuf::from).unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "--config" => config = value(),
+            "--record" => record = Some(value()),
+            "--replay" => watch = Some(value()),
+            "--bot" => command = Some(args.next().unwrap_or_else(|| usage())),
+            "--puzzle" => puzzle = Some(args.next().unwrap_or_else(|| usage())),
+            "--board" => board = Some(args.next().unwrap_or_else(|| usage())),
+            "--export" => export = Some(value()),
+            _ => usage(),
+        }
+    }
+    // Bots place pieces directly, there are no key presses to record
+    if command.is_some() && (record.is_some() || watch.is_some()) {
+        eprintln!("--bot can't be combined with --record or --replay");
+        process::exit(2);
+    }
+    // Replays only know the seed, not the board and pieces of a puzzle
+    if puzzle.is_some() && (record.is_some() || watch.is_some() || command.is_some()) {
+        eprintln!("--puzzle can't be combined with --record, --replay or --bot");
+        process::exit(2);
+    }
+    if board.is_some() && (record.is_some() || watch.is_some() || puzzle.is_some()) {
+        eprintln!("--board can't be combined with --record, --replay or --puzzle");
+        process::exit(2);
+    }
+
+    // A missing config is fine, the firmware version would use the defaults too
+    let settings = match fs::read_to_string(&config) {
+        Ok(src) => {
+            let (settings, errors) = Settings::parse(&src);
+            for error in errors.iter() {
+                eprintln!("{}: {}", config.display(), error);
+            }
+            settings
+        }
+        Err(_) => Settings::default(),
+    };
+
+    let replay = watch.map(|path| {
+        match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|src| Replay::parse(&src))
+        {
+            Ok(replay) => replay,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+    });
+
+    let seed = match replay.as_ref() {
+        Some(replay) => replay.seed,
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64),
+    };
+
+    let puzzle = puzzle.map(|arg| {
+        let result = if arg.contains("115@") {
+            Puzzle::from_fumen("fumen", &arg)
+        } else {
+            let path = PathBuf::from(&arg);
+            let name = path
+                .file_stem()
+                .map_or(arg.clone(), |name| name.to_string_lossy().into_owned());
+            fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|src| Puzzle::parse(&name, &src))
+        };
+        match result {
+            Ok(puzzle) => puzzle,
+            Err(e) => {
+                eprintln!("{}: {}", arg, e);
+                process::exit(1);
+            }
+        }
+    });
+
+    // The first page of a fumen, given as is or in a file
+    let board = board.map(|arg| {
+        let result = if arg.contains("115@") {
+            Ok(arg.clone())
+        } else {
+            fs::read_to_string(&arg).map_err(|e| e.to_string())
+        }
+        .and_then(|src| fumen::decode(&src));
+        match result {
+            Ok(pages) => pages[0].game(seed),
+            Err(e) => {
+                eprintln!("{}: {}", arg, e);
+                process::exit(1);
+            }
+        }
+    });
+
+    // Started before the terminal changes mode so a failing bot leaves it alone
+    let mut bot = command.map(|command| match External::spawn(&command) {
+        Ok(bot) => bot,
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    });
+
+    let raw = match RawMode::enable() {
+        Ok(raw) => raw,
+        Err(e) => {
+            eprintln!("stdin is not a terminal: {}", e);
+            process::exit(1);
+        }
+    };
+    let data = puzzle.as_ref().map(Puzzle::game).or(board);
+    let mut game = Game::new(settings, seed, data, puzzle);
+    let title = match (replay.as_ref(), bot.as_mut()) {
+        (Some(replay), _) => {
+            game.watch(replay);
+            String::from("Game over, press any key to quit")
+        }
+        (None, Some(bot)) => match game.autoplay(bot) {
+            Ok(()) => format!("{} is done, press any key to quit", bot.name),
+            Err(e) => format!("{}, press any key to quit", e),
+        },
+        (None, None) => {
+            game.play();
+            match (game.puzzle.as_ref(), game.outcome) {
+                (Some(puzzle), Some(Outcome::Solved)) => {
+                    format!("{} solved, press any key to quit", puzzle.name)
+                }
+                (Some(puzzle), Some(Outcome::Failed)) => {
+                    format!("{} failed, press any key to quit", puzzle.name)
+                }
+                (Some(puzzle), None) => format!("{} unsolved, press any key to quit", puzzle.name),
+                (None, _) => String::from("Game over, press any key to quit"),
+            }
+        }
+    };
+    game.show_results(&title);
+    drop(raw);
+    drop(bot);
+
+    if let Some(path) = record {
+        if let Err(e) = fs::write(&path, game.replay.serialize()) {
+            eprintln!("{}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+    if let Some(path) = export {
+        let page = Page::from_game(&game.player.data, game.settings.previews);
+        if let Err(e) = fs::write(&path, fumen::encode(&[page]) + "\n") {
+            eprintln!("{}: {}", path.display(), e);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use rustris_engine::ansi::Decoder;
+use rustris_engine::settings::KeyCode;
+use std::io::{self, Write};
+use std::mem;
+
+// Puts the terminal into raw mode until dropped
+pub struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    pub fn enable() -> io::Result<Self> {
+        unsafe {
+            let mut termios: libc::termios = mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let original = termios;
+            libc::cfmakeraw(&mut termios);
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self { original })
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+        // Reset colours, clear the screen and show the cursor again
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(b"\x1b[0m\x1b[2J\x1b[H\x1b[?25h");
+        let _ = stdout.flush();
+    }
+}
+
+// Columns and rows of the terminal on stdout
+pub fn size() -> Option<(usize, usize)> {
+    let mut size: libc::winsize = unsafe { mem::zeroed() };
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } != 0
+        || size.ws_col == 0
+    {
+        return None;
+    }
+    Some((size.ws_col as usize, size.ws_row as usize))
+}
+
+// Waits up to `timeout` ms for a byte on stdin
+fn read_byte(timeout: i32) -> Option<u8> {
+    let mut fds = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    if unsafe { libc::poll(&mut fds, 1, timeout) } <= 0 {
+        return None;
+    }
+    let mut byte = 0u8;
+    match unsafe { libc::read(libc::STDIN_FILENO, &mut byte as *mut u8 as *mut _, 1) } {
+        1 => Some(byte),
+        _ => None,
+    }
+}
+
+// Terminals only report key presses, held keys arrive as the terminal's own key repeat
+pub fn read_key(decoder: &mut Decoder, timeout: i32) -> Option<KeyCode> {
+    let mut timeout = timeout;
+    while let Some(byte) = read_byte(timeout) {
+        if let Some(key) = decoder.feed(byte) {
+            return Some(key);
+        }
+        // The rest of an escape sequence arrives right away
+        timeout = 10;
+    }
+    decoder.idle()
+}